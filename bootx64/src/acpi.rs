@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use common::kernelboot::record::Rsdp;
+use uefi::prelude::{Boot, SystemTable};
+use uefi::table::cfg::{ACPI2_GUID, ACPI_GUID};
+use x86_64::PhysAddr;
+
+/// Searches the configuration table for the RSDP. The one of ACPI 2.0 is preferred since it
+/// contains the address of XSDT.
+pub fn rsdp(system_table: &SystemTable<Boot>) -> Option<Rsdp> {
+    let addr = find(system_table, ACPI2_GUID).or_else(|| find(system_table, ACPI_GUID));
+
+    match addr {
+        Some(addr) => info!("RSDP: {:?}", addr),
+        None => warn!("No RSDP is found in the configuration table."),
+    }
+
+    addr.map(Rsdp::new)
+}
+
+fn find(system_table: &SystemTable<Boot>, guid: uefi::Guid) -> Option<PhysAddr> {
+    system_table
+        .config_table()
+        .iter()
+        .find(|entry| entry.guid == guid)
+        .map(|entry| PhysAddr::new(entry.address as u64))
+}
@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use common::kernelboot::record::CommandLine;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::table::boot;
+use uefi::{Handle, ResultExt};
+
+/// Returns the load options of this image, which are set by the boot manager or the UEFI shell.
+pub fn get(image: Handle, boot_services: &boot::BootServices) -> Option<CommandLine> {
+    let loaded_image = boot_services
+        .handle_protocol::<LoadedImage>(image)
+        .expect_success("Failed to get the loaded image protocol.");
+    let loaded_image = unsafe { &*loaded_image.get() };
+
+    let mut buf = [0; CommandLine::MAX_LEN];
+    match loaded_image.load_options(&mut buf) {
+        Ok(options) if !options.is_empty() => {
+            info!("Command line: {}", options);
+            Some(CommandLine::new(options))
+        }
+        _ => None,
+    }
+}
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use super::root_dir;
-use common::constant::{KERNEL_ADDR, KERNEL_NAME};
-use core::cmp;
+use common::constant::KERNEL_NAME;
+use common::mem::reserved::{self, MAX_KERNEL_SEGMENTS};
 use core::convert::TryFrom;
+use core::ptr;
 use core::slice;
-use elf_rs::Elf;
+use elf_rs::{Elf, ProgramType};
 use os_units::{Bytes, Size};
 use uefi::proto::media::file;
 use uefi::proto::media::file::File;
@@ -14,62 +15,195 @@ use uefi::proto::media::file::FileMode;
 use uefi::table::boot;
 use uefi::table::boot::{AllocateType, MemoryType};
 use uefi::ResultExt;
-use x86_64::structures::paging::Size4KiB;
+use x86_64::structures::paging::{PageSize, PageTableFlags, Size4KiB};
 use x86_64::{PhysAddr, VirtAddr};
 
 mod size;
 
-pub fn deploy(boot_services: &boot::BootServices) -> (PhysAddr, Size<Bytes>) {
+/// Loads every `PT_LOAD` segment of the kernel, records them in `reserved` and returns the entry
+/// address.
+pub fn deploy(boot_services: &boot::BootServices, reserved: &mut reserved::Map) -> VirtAddr {
     let mut root_dir = root_dir::open(boot_services);
 
-    locate(boot_services, &mut root_dir)
+    let image = read_image(boot_services, &mut root_dir);
+    let entry_addr = load(boot_services, image, reserved);
+
+    boot_services
+        .free_pool(image.as_mut_ptr())
+        .expect_success("Failed to free the kernel image");
+
+    entry_addr
 }
 
-fn locate(
+fn read_image(
     boot_services: &boot::BootServices,
     root_dir: &mut file::Directory,
-) -> (PhysAddr, Size<Bytes>) {
+) -> &'static mut [u8] {
     let kernel_bytes = size::get(root_dir);
     let mut kernel_handler = get_handler(root_dir);
 
-    let addr = allocate(boot_services, kernel_bytes);
-    put_on_memory(&mut kernel_handler, addr, kernel_bytes);
+    let buf = boot_services
+        .allocate_pool(MemoryType::LOADER_DATA, kernel_bytes.as_usize())
+        .expect_success("Failed to allocate memory for the kernel image");
+    let buf = unsafe { slice::from_raw_parts_mut(buf, kernel_bytes.as_usize()) };
 
-    (addr, kernel_bytes)
-}
+    put_on_memory(&mut kernel_handler, buf);
 
-pub fn fetch_entry_address_and_memory_size(
-    addr: PhysAddr,
-    bytes: Size<Bytes>,
-) -> (VirtAddr, Size<Bytes>) {
-    let elf =
-        Elf::from_bytes(unsafe { slice::from_raw_parts(addr.as_u64() as _, bytes.as_usize()) });
+    buf
+}
 
-    let elf = match elf {
-        Ok(elf) => elf,
+fn load(
+    boot_services: &boot::BootServices,
+    image: &[u8],
+    reserved: &mut reserved::Map,
+) -> VirtAddr {
+    let elf = match Elf::from_bytes(image) {
+        Ok(Elf::Elf64(elf)) => elf,
+        Ok(Elf::Elf32(_)) => panic!("32-bit kernel is not supported"),
         Err(e) => panic!("Could not get ELF information from the kernel: {:?}", e),
     };
 
-    match elf {
-        Elf::Elf32(_) => panic!("32-bit kernel is not supported"),
-        Elf::Elf64(elf) => {
-            let entry_addr = VirtAddr::new(elf.header().entry_point());
-            let mem_size = elf
-                .program_header_iter()
-                .fold(Size::<Bytes>::new(0), |acc, x| {
-                    cmp::max(
-                        acc,
-                        Size::new(usize::try_from(x.ph.vaddr() + x.ph.memsz()).unwrap()),
-                    )
-                })
-                - Size::<Bytes>::new(usize::try_from(KERNEL_ADDR.as_u64()).unwrap());
-
-            info!("Entry point: {:?}", entry_addr);
-            info!("Memory size: {:X?}", mem_size.as_usize());
-
-            (entry_addr, mem_size)
+    let mut segments = [Segment::default(); MAX_KERNEL_SEGMENTS];
+    let mut num_of_segments = 0;
+    for header in elf
+        .program_header_iter()
+        .filter(|header| header.ph.ph_type() == ProgramType::LOAD)
+    {
+        assert!(
+            num_of_segments < MAX_KERNEL_SEGMENTS,
+            "The kernel has too many loadable segments."
+        );
+
+        segments[num_of_segments] = Segment {
+            offset: header.ph.offset(),
+            vaddr: header.ph.vaddr(),
+            file_bytes: header.ph.filesz(),
+            mem_bytes: header.ph.memsz(),
+            flags: header.ph.flags(),
+        };
+        num_of_segments += 1;
+    }
+    let segments = &segments[..num_of_segments];
+    assert_no_conflicting_permissions(segments);
+
+    let (virt_start, virt_end) = virt_range(segments);
+    let bytes = Size::<Bytes>::new(usize::try_from(virt_end - virt_start).unwrap());
+    let phys_start = allocate(boot_services, bytes);
+
+    // Zeroing the whole range also clears `.bss` and the gaps between segments.
+    unsafe { ptr::write_bytes(phys_start.as_u64() as *mut u8, 0, bytes.as_usize()) }
+
+    for segment in segments {
+        segment.copy(image, phys_start + (segment.vaddr - virt_start.as_u64()));
+
+        let page_start = segment.page_start();
+        reserved.add_kernel_segment(
+            page_start,
+            phys_start + (page_start - virt_start),
+            Size::new(usize::try_from(segment.page_end() - page_start).unwrap()),
+            segment.page_flags(),
+        );
+
+        info!(
+            "Segment: {:X}..{:X} File size: {:X} Flags: {:?}",
+            segment.vaddr,
+            segment.vaddr + segment.mem_bytes,
+            segment.file_bytes,
+            segment.page_flags()
+        );
+    }
+
+    let entry_addr = VirtAddr::new(elf.header().entry_point());
+    info!("Entry point: {:?}", entry_addr);
+    info!("Memory size: {:X?}", bytes.as_usize());
+
+    entry_addr
+}
+
+fn virt_range(segments: &[Segment]) -> (VirtAddr, VirtAddr) {
+    let start = segments
+        .iter()
+        .map(Segment::page_start)
+        .min()
+        .expect("The kernel has no loadable segments");
+    let end = segments
+        .iter()
+        .map(Segment::page_end)
+        .max()
+        .expect("The kernel has no loadable segments");
+
+    (start, end)
+}
+
+// Pages shared by two segments can hold only one set of permissions.
+fn assert_no_conflicting_permissions(segments: &[Segment]) {
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            let share_page = a.page_start() < b.page_end() && b.page_start() < a.page_end();
+
+            assert!(
+                !share_page || a.page_flags() == b.page_flags(),
+                "Segments at {:X} and {:X} share a page with different permissions. Align them to 4 KiB in `os.ld`.",
+                a.vaddr,
+                b.vaddr
+            );
+        }
+    }
+}
+
+// See the `p_flags` field of the ELF program header.
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+
+#[derive(Copy, Clone, Default)]
+struct Segment {
+    offset: u64,
+    vaddr: u64,
+    file_bytes: u64,
+    mem_bytes: u64,
+    flags: u32,
+}
+
+impl Segment {
+    fn copy(&self, image: &[u8], phys: PhysAddr) {
+        let offset = usize::try_from(self.offset).unwrap();
+        let file_bytes = usize::try_from(self.file_bytes).unwrap();
+
+        assert!(
+            file_bytes <= usize::try_from(self.mem_bytes).unwrap(),
+            "The file size of a segment exceeds its memory size."
+        );
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                image[offset..offset + file_bytes].as_ptr(),
+                phys.as_u64() as *mut u8,
+                file_bytes,
+            );
         }
     }
+
+    fn page_start(&self) -> VirtAddr {
+        VirtAddr::new(self.vaddr).align_down(Size4KiB::SIZE)
+    }
+
+    fn page_end(&self) -> VirtAddr {
+        VirtAddr::new(self.vaddr + self.mem_bytes).align_up(Size4KiB::SIZE)
+    }
+
+    fn page_flags(&self) -> PageTableFlags {
+        let mut flags = PageTableFlags::PRESENT;
+
+        if self.flags & PF_W != 0 {
+            flags |= PageTableFlags::WRITABLE;
+        }
+
+        if self.flags & PF_X == 0 {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+
+        flags
+    }
 }
 
 fn get_handler(root_dir: &mut file::Directory) -> file::RegularFile {
@@ -92,17 +226,9 @@ fn allocate(boot_services: &boot::BootServices, kernel_bytes: Size<Bytes>) -> Ph
     )
 }
 
-fn put_on_memory(
-    handler: &mut file::RegularFile,
-    kernel_addr: PhysAddr,
-    kernel_bytes: Size<Bytes>,
-) {
+fn put_on_memory(handler: &mut file::RegularFile, buf: &mut [u8]) {
     // Reading should use while statement with the number of bytes which were actually read.
     // However, without while statement previous uefi implementation worked so this uefi
     // implementation also never use it.
-    handler
-        .read(unsafe {
-            core::slice::from_raw_parts_mut(kernel_addr.as_u64() as _, kernel_bytes.as_usize())
-        })
-        .expect_success("Failed to read kernel");
+    handler.read(buf).expect_success("Failed to read kernel");
 }
@@ -15,18 +15,23 @@ extern crate log;
 
 extern crate x86_64;
 
+mod acpi;
+mod cmdline;
 mod exit;
 mod fs;
 mod gop;
 mod mem;
 
-use common::{kernelboot, mem::reserved};
+use common::{
+    kernelboot::{self, record},
+    mem::reserved,
+};
 use core::{convert::TryFrom, ptr, ptr::NonNull, slice};
 use fs::kernel;
 use mem::{free_page, paging, stack};
 use uefi::{
     prelude::{Boot, Handle, SystemTable},
-    table::{boot, boot::MemoryType},
+    table::{boot, boot::MemoryType, Runtime},
     ResultExt,
 };
 
@@ -37,26 +42,48 @@ pub fn efi_main(image: Handle, system_table: SystemTable<Boot>) -> ! {
 
     let vram_info = gop::init(system_table.boot_services());
 
-    let (phys_kernel_addr, bytes_kernel) = kernel::deploy(system_table.boot_services());
-    let (entry_addr, actual_mem_size) =
-        kernel::fetch_entry_address_and_memory_size(phys_kernel_addr, bytes_kernel);
-
     let stack_addr = stack::allocate(system_table.boot_services());
     let free_page = free_page::allocate(system_table.boot_services());
-    let reserved_regions = reserved::Map::new(
-        &reserved::KernelPhysRange::new(phys_kernel_addr, actual_mem_size),
-        stack_addr,
-        &vram_info,
-        free_page,
-    );
-    let mem_map = terminate_boot_services(image, system_table);
-
-    exit::bootx64(kernelboot::Info::new(
-        entry_addr,
-        vram_info,
-        mem_map,
-        reserved_regions,
-    ));
+    let mut reserved_regions = reserved::Map::new(stack_addr, &vram_info, free_page);
+
+    let entry_addr = kernel::deploy(system_table.boot_services(), &mut reserved_regions);
+
+    let mut boot_info = kernelboot::Info::new(entry_addr, reserved_regions);
+    boot_info.add(vram_info);
+    if let Some(cmdline) = cmdline::get(image, system_table.boot_services()) {
+        boot_info.add(cmdline);
+    }
+    if let Some(rsdp) = acpi::rsdp(&system_table) {
+        boot_info.add(rsdp);
+    }
+    if let Some(boot_time) = fetch_boot_time(&system_table) {
+        boot_info.add(boot_time);
+    }
+
+    let (runtime_table, mem_map) = terminate_boot_services(image, system_table);
+    boot_info.add(mem_map);
+    boot_info.add(record::EfiSystemTable::new(&runtime_table));
+
+    exit::bootx64(boot_info);
+}
+
+fn fetch_boot_time(system_table: &SystemTable<Boot>) -> Option<record::BootTime> {
+    let time = system_table
+        .runtime_services()
+        .get_time()
+        .log_warning()
+        .ok()?;
+
+    Some(record::BootTime {
+        tsc: unsafe { core::arch::x86_64::_rdtsc() },
+        year: time.year(),
+        month: time.month(),
+        day: time.day(),
+        hour: time.hour(),
+        minute: time.minute(),
+        second: time.second(),
+        nanosecond: time.nanosecond(),
+    })
 }
 
 fn init_libs(system_table: &SystemTable<Boot>) {
@@ -76,7 +103,10 @@ fn reset_console(system_table: &SystemTable<Boot>) {
         .expect_success("Failed to reset stdout");
 }
 
-fn terminate_boot_services(image: Handle, system_table: SystemTable<Boot>) -> common::mem::Map {
+fn terminate_boot_services(
+    image: Handle,
+    system_table: SystemTable<Boot>,
+) -> (SystemTable<Runtime>, common::mem::Map) {
     info!("Goodbye, boot services...");
     let memory_map_buf = NonNull::new(
         system_table
@@ -104,7 +134,7 @@ fn terminate_boot_services(image: Handle, system_table: SystemTable<Boot>) -> co
         )
     };
 
-    let (_, descriptors_iter) = system_table
+    let (runtime_table, descriptors_iter) = system_table
         .exit_boot_services(image, buf_for_exiting)
         .expect("Failed to exit boot services")
         .unwrap();
@@ -123,5 +153,8 @@ fn terminate_boot_services(image: Handle, system_table: SystemTable<Boot>) -> co
         num_descriptors += 1;
     }
 
-    common::mem::Map::new(memory_map_buf, num_descriptors)
+    (
+        runtime_table,
+        common::mem::Map::new(memory_map_buf, num_descriptors),
+    )
 }
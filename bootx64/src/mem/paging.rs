@@ -9,9 +9,10 @@ use uefi::table::boot::{AllocateType, MemoryType};
 use x86_64::addr::PhysAddr;
 use x86_64::registers::control::Cr3;
 use x86_64::registers::control::{Cr0, Cr0Flags};
+use x86_64::registers::model_specific::{Efer, EferFlags};
 use x86_64::structures::paging::{
-    FrameAllocator, Mapper, Page, PageSize, PageTable, PageTableFlags, PhysFrame,
-    RecursivePageTable, Size4KiB,
+    mapper::MapToError, FrameAllocator, Mapper, Page, PageSize, PageTable, PageTableFlags,
+    PhysFrame, RecursivePageTable, Size4KiB,
 };
 
 struct AllocatorWithEfiMemoryMap<'a> {
@@ -41,6 +42,9 @@ unsafe impl<'a> FrameAllocator<Size4KiB> for AllocatorWithEfiMemoryMap<'a> {
 }
 
 pub fn init(boot_info: &mut kernelboot::Info) {
+    enable_no_execute();
+
+    // Some firmwares map their page tables as read-only.
     remove_table_protection();
 
     enable_recursive_mapping();
@@ -52,12 +56,27 @@ pub fn init(boot_info: &mut kernelboot::Info) {
     for region in reserved.iter() {
         map_virt_to_phys(region, &mut allocator);
     }
+
+    enable_write_protection();
 }
 
 fn enable_recursive_mapping() {
     let p4: &mut PageTable = unsafe { &mut *(get_pml4_addr().as_u64() as *mut _) };
 
-    p4[511].set_addr(get_pml4_addr(), PageTableFlags::PRESENT);
+    p4[511].set_addr(
+        get_pml4_addr(),
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+    );
+}
+
+// Pages of the kernel are mapped with `NO_EXECUTE`, which is a reserved bit unless `EFER.NXE` is
+// set.
+fn enable_no_execute() {
+    unsafe {
+        Efer::update(|flags| {
+            flags.insert(EferFlags::NO_EXECUTE_ENABLE);
+        })
+    }
 }
 
 fn remove_table_protection() {
@@ -68,27 +87,43 @@ fn remove_table_protection() {
     }
 }
 
+// With `CR0.WP` set, the supervisor mode can't write to read-only pages such as `.text` of the
+// kernel. The recursive mapping keeps the page tables writable.
+fn enable_write_protection() {
+    unsafe {
+        Cr0::update(|flags| {
+            flags.insert(Cr0Flags::WRITE_PROTECT);
+        })
+    }
+}
+
 fn map_virt_to_phys(region: &reserved::Range, allocator: &mut AllocatorWithEfiMemoryMap) {
     let p4 = unsafe { &mut *(RECUR_PML4_ADDR.as_mut_ptr()) };
     let mut p4 = RecursivePageTable::new(p4).unwrap();
 
     let num_of_pages = region.bytes().as_num_of_pages::<Size4KiB>().as_usize();
     for i in 0..num_of_pages {
-        unsafe {
+        let frame = PhysFrame::containing_address(
+            region.phys() + usize::try_from(Size4KiB::SIZE).unwrap() * i,
+        );
+        let result = unsafe {
             p4.map_to_with_table_flags::<AllocatorWithEfiMemoryMap>(
                 Page::<Size4KiB>::containing_address(
                     region.virt() + usize::try_from(Size4KiB::SIZE).unwrap() * i,
                 ),
-                PhysFrame::containing_address(
-                    region.phys() + usize::try_from(Size4KiB::SIZE).unwrap() * i,
-                ),
-                PageTableFlags::PRESENT,
-                PageTableFlags::PRESENT,
+                frame,
+                region.flags(),
+                PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
                 allocator,
             )
+        };
+
+        match result {
+            Ok(flush) => flush.flush(),
+            // Kernel segments which are not page-aligned share their boundary pages.
+            Err(MapToError::PageAlreadyMapped(mapped)) if mapped == frame => {}
+            Err(e) => panic!("Failed to map a reserved region: {:?}", e),
         }
-        .unwrap()
-        .flush();
     }
 }
 
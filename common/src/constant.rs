@@ -23,12 +23,19 @@ pub const VRAM_ADDR: VirtAddr = VirtAddr::new_truncate(0xffff_ffff_a000_1000);
 pub const STACK_BASE: VirtAddr = VirtAddr::new_truncate(0xffff_ffff_c000_0000);
 pub const STACK_LOWER: VirtAddr =
     VirtAddr::new_truncate(STACK_BASE.as_u64() - NUM_OF_PAGES_STACK.as_bytes().as_usize() as u64);
+// Nothing is mapped here, so that a stack overflow causes a page fault instead of corrupting the
+// memory.
+pub const STACK_GUARD_PAGE: VirtAddr =
+    VirtAddr::new_truncate(STACK_LOWER.as_u64() - Size4KiB::SIZE);
 pub const INIT_RSP: VirtAddr = VirtAddr::new_truncate(STACK_BASE.as_u64() - Size4KiB::SIZE);
+// The kernel maps physical ranges such as ACPI tables and MMIO registers here.
+pub const KERNEL_MAP_ADDR: VirtAddr = VirtAddr::new_truncate(0xffff_ffff_b000_0000);
 pub const RECUR_PML4_ADDR: VirtAddr = VirtAddr::new_truncate(0xffff_ffff_ffff_f000);
 pub const LIMIT_VIRT_ADDR: VirtAddr = VirtAddr::new_truncate(0x1_0000_0000_0000);
 
 pub const NUM_OF_PAGES_STACK: Size<NumOfPages<Size4KiB>> = Size::new(16);
 pub const BYTES_KERNEL_HEAP: Size<Bytes> = Size::new(0x1000_0000);
+pub const BYTES_KERNEL_MAP: Size<Bytes> = Size::new(0x0f00_0000);
 
 pub const PORT_KEY_STATUS: Port<u8> = Port::new(0x0064);
 pub const PORT_KEY_CMD: Port<u8> = Port::new(0x0064);
@@ -36,6 +43,7 @@ pub const PORT_KEY_DATA: Port<u8> = Port::new(0x0060);
 
 pub const KEY_CMD_WRITE_MODE: u8 = 0x60;
 pub const KEY_CMD_MODE: u8 = 0x47;
+pub const KEY_CMD_PULSE_RESET: u8 = 0xfe;
 pub const KEY_STATUS_SEND_NOT_READY: u8 = 0x02;
 
 pub const KERNEL_NAME: &str = "kernel.bin";
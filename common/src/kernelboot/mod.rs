@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod record;
+
+use crate::{constant::INIT_RSP, mem, mem::reserved, vram};
+use core::{convert::TryFrom, mem::size_of, ptr};
+use record::{Record, Records, Tag};
+use uefi::table::boot;
+use x86_64::VirtAddr;
+
+pub const MAGIC: u64 = 0x544f_424e_454d_4152; // "RAMENBOT" in little endian.
+pub const VERSION: u32 = 1;
+
+// `Info` is placed on the top page of the kernel stack, so it must fit in 4 KiB.
+//
+// The header and the records come first. Their layout must not change between versions so that
+// the kernel can always read the version and the framebuffer to report a mismatch.
+#[repr(C)]
+pub struct Info {
+    magic: u64,
+    version: u32,
+    bytes: u32,
+    records: Records,
+    entry_addr: VirtAddr,
+    reserved: reserved::Map,
+}
+
+impl Info {
+    #[must_use]
+    pub fn new(entry_addr: VirtAddr, reserved: reserved::Map) -> Self {
+        Self {
+            magic: MAGIC,
+            version: VERSION,
+            bytes: u32::try_from(size_of::<Self>()).unwrap(),
+            records: Records::new(),
+            entry_addr,
+            reserved,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// This method panics if there is no space for `record`.
+    pub fn add<T: Record>(&mut self, record: T) {
+        self.records.push(record)
+    }
+
+    /// Returns the first record of type `T`.
+    #[must_use]
+    pub fn record<T: Record>(&self) -> Option<T> {
+        self.records::<T>().next()
+    }
+
+    pub fn records<T: Record>(&self) -> impl Iterator<Item = T> + '_ {
+        let valid = self.magic == MAGIC;
+        self.records.iter().filter(move |_| valid)
+    }
+
+    /// # Errors
+    ///
+    /// This method returns an error if the bootloader and the kernel disagree on the boot protocol,
+    /// or if a record which the kernel needs is missing.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.magic != MAGIC {
+            return Err(Error::InvalidMagic(self.magic));
+        }
+
+        if self.version != VERSION {
+            return Err(Error::VersionMismatch {
+                kernel: VERSION,
+                bootloader: self.version,
+            });
+        }
+
+        if self.bytes as usize != size_of::<Self>() {
+            return Err(Error::SizeMismatch {
+                kernel: size_of::<Self>(),
+                bootloader: self.bytes as usize,
+            });
+        }
+
+        if self.record::<vram::Info>().is_none() {
+            return Err(Error::MissingRecord(Tag::FRAMEBUFFER));
+        }
+
+        if self.record::<mem::Map>().is_none() {
+            return Err(Error::MissingRecord(Tag::MEMORY_MAP));
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn entry_addr(&self) -> VirtAddr {
+        self.entry_addr
+    }
+
+    /// # Panics
+    ///
+    /// This method panics if the bootloader passed no framebuffer.
+    #[must_use]
+    pub fn vram(&self) -> vram::Info {
+        self.record().expect("No framebuffer is passed.")
+    }
+
+    pub fn set(self) {
+        unsafe {
+            ptr::write(INIT_RSP.as_mut_ptr() as _, self);
+        }
+    }
+
+    #[must_use]
+    pub fn get() -> Self {
+        unsafe { ptr::read(INIT_RSP.as_mut_ptr() as _) }
+    }
+
+    /// # Panics
+    ///
+    /// This method panics if the bootloader passed no memory map.
+    #[must_use]
+    pub fn mem_map(&mut self) -> &mut [boot::MemoryDescriptor] {
+        let map = self.record::<mem::Map>().expect("No memory map is passed.");
+
+        // `&mut self` prevents the descriptors from being borrowed twice.
+        unsafe { map.into_slice() }
+    }
+
+    #[must_use]
+    pub fn reserved(&self) -> &reserved::Map {
+        &self.reserved
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidMagic(u64),
+    VersionMismatch { kernel: u32, bootloader: u32 },
+    SizeMismatch { kernel: usize, bootloader: usize },
+    MissingRecord(Tag),
+}
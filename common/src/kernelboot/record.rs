@@ -0,0 +1,286 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{mem, vram},
+    core::{
+        convert::TryFrom,
+        fmt,
+        mem::{self, size_of},
+        ptr, str,
+    },
+    os_units::{Bytes, Size},
+    uefi::table::{Runtime, SystemTable},
+    x86_64::PhysAddr,
+};
+
+const CAPACITY: usize = 1024;
+const ALIGN: usize = 8;
+
+/// A piece of information which the bootloader may pass to the kernel.
+///
+/// Each record is stored with its tag and size, so the kernel can skip the records it does not
+/// know. Add a new tag instead of changing the layout of an existing record.
+pub trait Record: Copy {
+    const TAG: Tag;
+}
+
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Tag(u32);
+impl Tag {
+    pub const FRAMEBUFFER: Self = Self(1);
+    pub const MEMORY_MAP: Self = Self(2);
+    pub const ACPI_RSDP: Self = Self(3);
+    pub const COMMAND_LINE: Self = Self(4);
+    pub const MODULE: Self = Self(5);
+    pub const BOOT_TIME: Self = Self(6);
+    pub const EFI_SYSTEM_TABLE: Self = Self(7);
+}
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Self::FRAMEBUFFER => "FRAMEBUFFER",
+            Self::MEMORY_MAP => "MEMORY_MAP",
+            Self::ACPI_RSDP => "ACPI_RSDP",
+            Self::COMMAND_LINE => "COMMAND_LINE",
+            Self::MODULE => "MODULE",
+            Self::BOOT_TIME => "BOOT_TIME",
+            Self::EFI_SYSTEM_TABLE => "EFI_SYSTEM_TABLE",
+            Self(n) => return write!(f, "Tag({})", n),
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl Record for vram::Info {
+    const TAG: Tag = Tag::FRAMEBUFFER;
+}
+
+impl Record for mem::Map {
+    const TAG: Tag = Tag::MEMORY_MAP;
+}
+
+/// The physical address of the ACPI Root System Description Pointer.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Rsdp(PhysAddr);
+impl Rsdp {
+    #[must_use]
+    pub fn new(addr: PhysAddr) -> Self {
+        Self(addr)
+    }
+
+    #[must_use]
+    pub fn addr(self) -> PhysAddr {
+        self.0
+    }
+}
+impl Record for Rsdp {
+    const TAG: Tag = Tag::ACPI_RSDP;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CommandLine {
+    buf: [u8; Self::MAX_LEN],
+    len: usize,
+}
+impl CommandLine {
+    pub const MAX_LEN: usize = 256;
+
+    /// Longer strings are truncated to `MAX_LEN` bytes at a character boundary.
+    #[must_use]
+    pub fn new(s: &str) -> Self {
+        let mut len = s.len().min(Self::MAX_LEN);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        let mut buf = [0; Self::MAX_LEN];
+        buf[..len].copy_from_slice(&s.as_bytes()[..len]);
+
+        Self { buf, len }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+impl Record for CommandLine {
+    const TAG: Tag = Tag::COMMAND_LINE;
+}
+
+/// A file which the bootloader loaded on the memory in addition to the kernel.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Module {
+    start: PhysAddr,
+    bytes: Size<Bytes>,
+    name: [u8; Self::MAX_NAME_LEN],
+    name_len: usize,
+}
+impl Module {
+    pub const MAX_NAME_LEN: usize = 32;
+
+    /// # Panics
+    ///
+    /// This method panics if `name` is longer than `MAX_NAME_LEN` bytes.
+    #[must_use]
+    pub fn new(start: PhysAddr, bytes: Size<Bytes>, name: &str) -> Self {
+        assert!(name.len() <= Self::MAX_NAME_LEN, "Module name is too long.");
+
+        let mut buf = [0; Self::MAX_NAME_LEN];
+        buf[..name.len()].copy_from_slice(name.as_bytes());
+
+        Self {
+            start,
+            bytes,
+            name: buf,
+            name_len: name.len(),
+        }
+    }
+
+    #[must_use]
+    pub fn start(&self) -> PhysAddr {
+        self.start
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> Size<Bytes> {
+        self.bytes
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+}
+impl Record for Module {
+    const TAG: Tag = Tag::MODULE;
+}
+
+/// The wall-clock time and the time stamp counter read just before exiting boot services.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BootTime {
+    pub tsc: u64,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+}
+impl Record for BootTime {
+    const TAG: Tag = Tag::BOOT_TIME;
+}
+
+/// The UEFI system table after exiting boot services.
+///
+/// The bootloader does not call `SetVirtualAddressMap`, so the runtime services must be called with
+/// the runtime regions identity-mapped.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct EfiSystemTable(u64);
+impl EfiSystemTable {
+    #[must_use]
+    pub fn new(table: &SystemTable<Runtime>) -> Self {
+        Self(table.get_current_system_table_addr())
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that the runtime regions are identity-mapped while using the
+    /// returned table, and that the runtime services are not called concurrently.
+    #[must_use]
+    pub unsafe fn get(self) -> SystemTable<Runtime> {
+        // `SystemTable` is a pointer to the table. The crate has no constructor from an address.
+        mem::transmute::<u64, SystemTable<Runtime>>(self.0)
+    }
+}
+impl Record for EfiSystemTable {
+    const TAG: Tag = Tag::EFI_SYSTEM_TABLE;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Header {
+    tag: Tag,
+    bytes: u32,
+}
+
+// `len` comes first so that the records can be found even if `CAPACITY` changes.
+#[repr(C)]
+pub(super) struct Records {
+    len: usize,
+    buf: [u8; CAPACITY],
+}
+impl Records {
+    pub(super) fn new() -> Self {
+        Self {
+            len: 0,
+            buf: [0; CAPACITY],
+        }
+    }
+
+    pub(super) fn push<T: Record>(&mut self, record: T) {
+        let start = self.len;
+        let payload = start + size_of::<Header>();
+        let end = round_up(payload + size_of::<T>());
+
+        assert!(end <= CAPACITY, "No space for the {:?} record.", T::TAG);
+
+        let header = Header {
+            tag: T::TAG,
+            bytes: u32::try_from(size_of::<T>()).unwrap(),
+        };
+
+        unsafe {
+            ptr::write_unaligned(self.buf[start..].as_mut_ptr().cast(), header);
+            ptr::write_unaligned(self.buf[payload..].as_mut_ptr().cast(), record);
+        }
+
+        self.len = end;
+    }
+
+    pub(super) fn iter<T: Record>(&self) -> impl Iterator<Item = T> + '_ {
+        self.headers()
+            .filter(|(header, _)| header.tag == T::TAG)
+            .filter_map(move |(header, payload)| {
+                // A record of a different size is from an incompatible bootloader.
+                if header.bytes as usize == size_of::<T>() {
+                    Some(unsafe { ptr::read_unaligned(self.buf[payload..].as_ptr().cast()) })
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn headers(&self) -> impl Iterator<Item = (Header, usize)> + '_ {
+        let len = self.len.min(CAPACITY);
+        let mut offset = 0;
+
+        core::iter::from_fn(move || {
+            let payload = offset + size_of::<Header>();
+            if payload > len {
+                return None;
+            }
+
+            let header: Header = unsafe { ptr::read_unaligned(self.buf[offset..].as_ptr().cast()) };
+            let end = round_up(payload + header.bytes as usize);
+            if end > len {
+                return None;
+            }
+
+            offset = end;
+            Some((header, payload))
+        })
+    }
+}
+
+fn round_up(n: usize) -> usize {
+    (n + ALIGN - 1) / ALIGN * ALIGN
+}
@@ -6,6 +6,7 @@ use core::{ptr::NonNull, slice};
 use uefi::table::boot;
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub struct Map {
     ptr: NonNull<boot::MemoryDescriptor>,
     num_descriptors: usize,
@@ -20,8 +21,12 @@ impl Map {
         }
     }
 
+    /// # Safety
+    ///
+    /// The caller must ensure that the descriptors are not borrowed through another copy of this
+    /// map while the returned slice is alive.
     #[must_use]
-    pub fn as_slice(&mut self) -> &mut [boot::MemoryDescriptor] {
-        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.num_descriptors) }
+    pub unsafe fn into_slice<'a>(self) -> &'a mut [boot::MemoryDescriptor] {
+        slice::from_raw_parts_mut(self.ptr.as_ptr(), self.num_descriptors)
     }
 }
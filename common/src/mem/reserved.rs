@@ -2,50 +2,67 @@
 
 use {
     crate::{
-        constant::{FREE_PAGE_ADDR, KERNEL_ADDR, NUM_OF_PAGES_STACK, STACK_LOWER, VRAM_ADDR},
+        constant::{FREE_PAGE_ADDR, NUM_OF_PAGES_STACK, STACK_LOWER, VRAM_ADDR},
         vram,
     },
     os_units::{Bytes, Size},
-    x86_64::{PhysAddr, VirtAddr},
+    x86_64::{structures::paging::PageTableFlags, PhysAddr, VirtAddr},
 };
 
-pub struct KernelPhysRange {
-    start: PhysAddr,
-    bytes: Size<Bytes>,
-}
+pub const MAX_KERNEL_SEGMENTS: usize = 8;
 
-impl KernelPhysRange {
-    #[must_use]
-    pub fn new(start: PhysAddr, bytes: Size<Bytes>) -> Self {
-        Self { start, bytes }
-    }
-}
+// Stack, VRAM and the free page.
+const NUM_OF_FIXED_RANGES: usize = 3;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
-pub struct Map([Range; 4]);
+pub struct Map {
+    ranges: [Range; NUM_OF_FIXED_RANGES + MAX_KERNEL_SEGMENTS],
+    len: usize,
+}
 impl Map {
     #[must_use]
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        kernel: &KernelPhysRange,
-        phys_addr_stack: PhysAddr,
-        vram: &vram::Info,
-        free_page: PhysAddr,
-    ) -> Self {
+    pub fn new(phys_addr_stack: PhysAddr, vram: &vram::Info, free_page: PhysAddr) -> Self {
+        let mut ranges = [Range::null(); NUM_OF_FIXED_RANGES + MAX_KERNEL_SEGMENTS];
+        ranges[0] = Range::stack(phys_addr_stack);
+        ranges[1] = Range::vram(vram);
+        ranges[2] = Range::free_page(free_page);
+
         Self {
-            0: [
-                Range::kernel(&kernel),
-                Range::stack(phys_addr_stack),
-                Range::vram(vram),
-                Range::free_page(free_page),
-            ],
+            ranges,
+            len: NUM_OF_FIXED_RANGES,
         }
     }
 
+    /// # Panics
+    ///
+    /// This method panics if more than `MAX_KERNEL_SEGMENTS` segments are added.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_kernel_segment(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        bytes: Size<Bytes>,
+        flags: PageTableFlags,
+    ) {
+        assert!(
+            self.len < self.ranges.len(),
+            "The kernel has more than {} loadable segments.",
+            MAX_KERNEL_SEGMENTS
+        );
+
+        self.ranges[self.len] = Range {
+            virt,
+            phys,
+            bytes,
+            flags,
+        };
+        self.len += 1;
+    }
+
     #[must_use]
     pub fn iter(&self) -> impl ExactSizeIterator<Item = &Range> {
-        self.0.iter()
+        self.ranges[..self.len].iter()
     }
 }
 
@@ -55,15 +72,17 @@ pub struct Range {
     virt: VirtAddr,
     phys: PhysAddr,
     bytes: Size<Bytes>,
+    flags: PageTableFlags,
 }
 
 impl Range {
     #[must_use]
-    fn kernel(kernel: &KernelPhysRange) -> Self {
+    fn null() -> Self {
         Self {
-            virt: KERNEL_ADDR,
-            phys: kernel.start,
-            bytes: kernel.bytes,
+            virt: VirtAddr::zero(),
+            phys: PhysAddr::zero(),
+            bytes: Size::new(0),
+            flags: PageTableFlags::empty(),
         }
     }
 
@@ -73,6 +92,7 @@ impl Range {
             virt: VRAM_ADDR,
             phys: vram.phys_ptr(),
             bytes: vram.bytes(),
+            flags: Self::data_flags(),
         }
     }
 
@@ -82,6 +102,7 @@ impl Range {
             virt: STACK_LOWER,
             phys,
             bytes: NUM_OF_PAGES_STACK.as_bytes(),
+            flags: Self::data_flags(),
         }
     }
 
@@ -91,9 +112,14 @@ impl Range {
             virt: FREE_PAGE_ADDR,
             phys,
             bytes: Size::new(0x1000),
+            flags: Self::data_flags(),
         }
     }
 
+    fn data_flags() -> PageTableFlags {
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE
+    }
+
     #[must_use]
     pub fn virt(&self) -> VirtAddr {
         self.virt
@@ -108,4 +134,9 @@ impl Range {
     pub fn bytes(&self) -> Size<Bytes> {
         self.bytes
     }
+
+    #[must_use]
+    pub fn flags(&self) -> PageTableFlags {
+        self.flags
+    }
 }
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::sdt::{Table, HEADER_BYTES};
+
+// See ACPI Specification 6.3, 20.2.
+const NAME_OP: u8 = 0x08;
+const ROOT_CHAR: u8 = b'\\';
+const PACKAGE_OP: u8 = 0x12;
+const BYTE_PREFIX: u8 = 0x0a;
+
+/// The values of `SLP_TYPa` and `SLP_TYPb` to enter a sleeping state.
+#[derive(Copy, Clone, Debug)]
+pub struct SleepType {
+    a: u8,
+    b: u8,
+}
+
+impl SleepType {
+    /// Finds the `_S5_` object in the AML code without interpreting it.
+    ///
+    /// This works as long as `_S5_` is defined as a package of constants, which is true for the
+    /// firmwares seen so far.
+    pub fn s5(dsdt: &Table) -> Option<Self> {
+        let aml = dsdt.bytes().get(HEADER_BYTES..)?;
+
+        let name = aml
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == b"_S5_")
+            .map(|(i, _)| i)
+            .find(|i| is_name_definition(aml, *i))?;
+
+        let mut i = name + 4;
+        if *aml.get(i)? != PACKAGE_OP {
+            return None;
+        }
+        i += 1;
+
+        // The upper two bits of the lead byte are the number of the following bytes of PkgLength.
+        i += usize::from(aml.get(i)? >> 6) + 1;
+
+        // NumElements.
+        i += 1;
+
+        let a = integer(aml, &mut i)?;
+        let b = integer(aml, &mut i)?;
+
+        Some(Self { a, b })
+    }
+
+    pub fn a(self) -> u8 {
+        self.a
+    }
+
+    pub fn b(self) -> u8 {
+        self.b
+    }
+}
+
+fn is_name_definition(aml: &[u8], name: usize) -> bool {
+    match name {
+        0 => false,
+        1 => aml[0] == NAME_OP,
+        _ => aml[name - 1] == NAME_OP || (aml[name - 2] == NAME_OP && aml[name - 1] == ROOT_CHAR),
+    }
+}
+
+// `ZeroOp` and `OneOp` are 0 and 1, so they can be read as their values.
+fn integer(aml: &[u8], i: &mut usize) -> Option<u8> {
+    if *aml.get(*i)? == BYTE_PREFIX {
+        *i += 1;
+    }
+
+    let value = *aml.get(*i)?;
+    *i += 1;
+    Some(value)
+}
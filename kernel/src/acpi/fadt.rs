@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{gas::GenericAddress, sdt::Table},
+    core::convert::TryFrom,
+    x86_64::{instructions::port::Port, PhysAddr},
+};
+
+// See ACPI Specification 6.3, Table 5-33.
+const OFFSET_DSDT: usize = 40;
+const OFFSET_SCI_INT: usize = 46;
+const OFFSET_SMI_CMD: usize = 48;
+const OFFSET_ACPI_ENABLE: usize = 52;
+const OFFSET_PM1A_CNT_BLK: usize = 64;
+const OFFSET_PM1B_CNT_BLK: usize = 68;
+const OFFSET_PM_TMR_BLK: usize = 76;
+const OFFSET_CENTURY: usize = 108;
+const OFFSET_IAPC_BOOT_ARCH: usize = 109;
+const OFFSET_FLAGS: usize = 112;
+const OFFSET_RESET_REG: usize = 116;
+const OFFSET_RESET_VALUE: usize = 128;
+const OFFSET_X_DSDT: usize = 140;
+const OFFSET_X_PM1A_CNT_BLK: usize = 172;
+const OFFSET_X_PM1B_CNT_BLK: usize = 184;
+
+const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+const IAPC_BOOT_ARCH_8042: u16 = 1 << 1;
+
+/// Fixed ACPI Description Table. Fields which older revisions lack are `None`.
+#[derive(Debug)]
+pub struct Fadt {
+    dsdt: PhysAddr,
+    sci_int: u16,
+    smi_cmd: u32,
+    acpi_enable: u8,
+    pm1a_cnt: Option<PmRegister>,
+    pm1b_cnt: Option<PmRegister>,
+    pm_tmr_blk: Option<u16>,
+    century: Option<u8>,
+    iapc_boot_arch: Option<u16>,
+    reset: Option<(GenericAddress, u8)>,
+}
+
+/// A PM1 control block, which is either a 32-bit I/O port or an extended generic address.
+#[derive(Copy, Clone, Debug)]
+pub enum PmRegister {
+    Port(u16),
+    Extended(GenericAddress),
+}
+
+impl PmRegister {
+    /// # Safety
+    ///
+    /// Reading a register may have side effects.
+    #[allow(clippy::cast_possible_truncation)]
+    pub unsafe fn read(&self) -> u16 {
+        match self {
+            Self::Port(port) => Port::<u16>::new(*port).read(),
+            Self::Extended(address) => address.read() as u16,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Writing to a register may have any side effects.
+    pub unsafe fn write(&self, value: u16) {
+        match self {
+            Self::Port(port) => Port::<u16>::new(*port).write(value),
+            Self::Extended(address) => address.write(u64::from(value)),
+        }
+    }
+}
+
+impl Fadt {
+    pub fn new(table: &Table) -> Option<Self> {
+        let bytes = table.bytes();
+
+        let dsdt = match table.field::<u64>(OFFSET_X_DSDT) {
+            Some(addr) if addr != 0 => addr,
+            _ => u64::from(table.field::<u32>(OFFSET_DSDT)?),
+        };
+
+        let flags = table.field::<u32>(OFFSET_FLAGS).unwrap_or(0);
+        let reset = if flags & FLAG_RESET_REG_SUP == 0 {
+            None
+        } else {
+            GenericAddress::parse(bytes, OFFSET_RESET_REG)
+                .zip(table.field::<u8>(OFFSET_RESET_VALUE))
+        };
+
+        Some(Self {
+            dsdt: PhysAddr::new(dsdt),
+            sci_int: table.field(OFFSET_SCI_INT)?,
+            smi_cmd: table.field(OFFSET_SMI_CMD)?,
+            acpi_enable: table.field(OFFSET_ACPI_ENABLE)?,
+            pm1a_cnt: pm_register(table, OFFSET_X_PM1A_CNT_BLK, OFFSET_PM1A_CNT_BLK),
+            pm1b_cnt: pm_register(table, OFFSET_X_PM1B_CNT_BLK, OFFSET_PM1B_CNT_BLK),
+            pm_tmr_blk: port(table.field(OFFSET_PM_TMR_BLK)?),
+            century: table
+                .field::<u8>(OFFSET_CENTURY)
+                .filter(|index| *index != 0),
+            iapc_boot_arch: table.field(OFFSET_IAPC_BOOT_ARCH),
+            reset,
+        })
+    }
+
+    pub fn dsdt(&self) -> PhysAddr {
+        self.dsdt
+    }
+
+    pub fn sci_int(&self) -> u16 {
+        self.sci_int
+    }
+
+    /// The port and the value to write to it to transfer the control of ACPI from SMM to the OS.
+    /// `None` if ACPI is always enabled, which is the case for hardware-reduced ACPI.
+    pub fn acpi_enable_command(&self) -> Option<(u16, u8)> {
+        if self.smi_cmd == 0 || self.acpi_enable == 0 {
+            None
+        } else {
+            Some((port(self.smi_cmd)?, self.acpi_enable))
+        }
+    }
+
+    pub fn pm1a_cnt(&self) -> Option<PmRegister> {
+        self.pm1a_cnt
+    }
+
+    pub fn pm1b_cnt(&self) -> Option<PmRegister> {
+        self.pm1b_cnt
+    }
+
+    /// The port of the ACPI PM timer, which counts at 3.579545 MHz.
+    pub fn pm_timer_port(&self) -> Option<u16> {
+        self.pm_tmr_blk
+    }
+
+    /// The index of the century register in the CMOS RAM.
+    pub fn century(&self) -> Option<u8> {
+        self.century
+    }
+
+    /// Whether the system has the 8042 keyboard controller. Firmwares before ACPI 2.0 do not
+    /// report it, in which case it is assumed to exist.
+    pub fn has_8042(&self) -> bool {
+        self.iapc_boot_arch
+            .map_or(true, |arch| arch & IAPC_BOOT_ARCH_8042 != 0)
+    }
+
+    /// The reset register and the value to write to it.
+    pub fn reset(&self) -> Option<(GenericAddress, u8)> {
+        self.reset
+    }
+}
+
+fn pm_register(table: &Table, extended: usize, legacy: usize) -> Option<PmRegister> {
+    match GenericAddress::parse(table.bytes(), extended) {
+        Some(address) => Some(PmRegister::Extended(address)),
+        None => port(table.field(legacy)?).map(PmRegister::Port),
+    }
+}
+
+// Legacy blocks hold 32-bit port numbers, but only 16 bits are valid for I/O ports.
+fn port(addr: u32) -> Option<u16> {
+    if addr == 0 {
+        None
+    } else {
+        u16::try_from(addr).ok()
+    }
+}
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::sdt,
+    crate::mem::allocator::virt,
+    core::{convert::TryFrom, ptr},
+    os_units::Size,
+    x86_64::{instructions::port::Port, PhysAddr},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressSpace {
+    SystemMemory,
+    SystemIo,
+    PciConfig,
+    Other(u8),
+}
+
+impl From<u8> for AddressSpace {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::SystemMemory,
+            1 => Self::SystemIo,
+            2 => Self::PciConfig,
+            n => Self::Other(n),
+        }
+    }
+}
+
+/// Generic Address Structure. See ACPI Specification 6.3, 5.2.3.2.
+#[derive(Copy, Clone, Debug)]
+pub struct GenericAddress {
+    space: AddressSpace,
+    bit_width: u8,
+    bit_offset: u8,
+    access_size: u8,
+    address: u64,
+}
+
+impl GenericAddress {
+    pub const BYTES: usize = 12;
+
+    /// Returns `None` if the structure does not exist or its address is zero, which means the
+    /// register is not supported.
+    pub fn parse(bytes: &[u8], offset: usize) -> Option<Self> {
+        let address: u64 = sdt::read(bytes, offset + 4)?;
+        if address == 0 {
+            return None;
+        }
+
+        Some(Self {
+            space: AddressSpace::from(sdt::read::<u8>(bytes, offset)?),
+            bit_width: sdt::read(bytes, offset + 1)?,
+            bit_offset: sdt::read(bytes, offset + 2)?,
+            access_size: sdt::read(bytes, offset + 3)?,
+            address,
+        })
+    }
+
+    pub fn space(&self) -> AddressSpace {
+        self.space
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// # Safety
+    ///
+    /// Reading a register may have side effects.
+    pub unsafe fn read(&self) -> u64 {
+        match self.space {
+            AddressSpace::SystemIo => match self.width() {
+                Width::U8 => u64::from(Port::<u8>::new(self.port()).read()),
+                Width::U16 => u64::from(Port::<u16>::new(self.port()).read()),
+                Width::U32 | Width::U64 => u64::from(Port::<u32>::new(self.port()).read()),
+            },
+            AddressSpace::SystemMemory => {
+                let bytes = Size::new(self.width().bytes());
+                let virt = virt::map(PhysAddr::new(self.address), bytes);
+                let value = match self.width() {
+                    Width::U8 => u64::from(ptr::read_volatile(virt.as_ptr::<u8>())),
+                    Width::U16 => u64::from(ptr::read_volatile(virt.as_ptr::<u16>())),
+                    Width::U32 => u64::from(ptr::read_volatile(virt.as_ptr::<u32>())),
+                    Width::U64 => ptr::read_volatile(virt.as_ptr::<u64>()),
+                };
+                virt::unmap(virt, bytes);
+                value
+            }
+            space => {
+                warn!("Reading from {:?} is not supported.", space);
+                0
+            }
+        }
+    }
+
+    /// `value` is truncated to the width of the register.
+    ///
+    /// # Safety
+    ///
+    /// Writing to a register may have any side effects.
+    #[allow(clippy::cast_possible_truncation)]
+    pub unsafe fn write(&self, value: u64) {
+        match self.space {
+            AddressSpace::SystemIo => match self.width() {
+                Width::U8 => Port::<u8>::new(self.port()).write(value as u8),
+                Width::U16 => Port::<u16>::new(self.port()).write(value as u16),
+                Width::U32 | Width::U64 => Port::<u32>::new(self.port()).write(value as u32),
+            },
+            AddressSpace::SystemMemory => {
+                let bytes = Size::new(self.width().bytes());
+                let virt = virt::map(PhysAddr::new(self.address), bytes);
+                match self.width() {
+                    Width::U8 => ptr::write_volatile(virt.as_mut_ptr::<u8>(), value as u8),
+                    Width::U16 => ptr::write_volatile(virt.as_mut_ptr::<u16>(), value as u16),
+                    Width::U32 => ptr::write_volatile(virt.as_mut_ptr::<u32>(), value as u32),
+                    Width::U64 => ptr::write_volatile(virt.as_mut_ptr::<u64>(), value),
+                }
+                virt::unmap(virt, bytes);
+            }
+            space => warn!("Writing to {:?} is not supported.", space),
+        }
+    }
+
+    fn port(&self) -> u16 {
+        u16::try_from(self.address).expect("The I/O port number is too large.")
+    }
+
+    // Old tables leave the access size undefined. Then the bit width tells the size.
+    fn width(&self) -> Width {
+        match (self.access_size, self.bit_width) {
+            (1, _) | (0, 0..=8) => Width::U8,
+            (2, _) | (0, 9..=16) => Width::U16,
+            (3, _) | (0, 17..=32) => Width::U32,
+            _ => Width::U64,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl Width {
+    fn bytes(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+            Self::U64 => 8,
+        }
+    }
+}
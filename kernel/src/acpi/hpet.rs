@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{
+        gas::{AddressSpace, GenericAddress},
+        sdt::{Table, HEADER_BYTES},
+    },
+    core::convert::TryFrom,
+};
+
+const OFFSET_EVENT_TIMER_BLOCK_ID: usize = HEADER_BYTES;
+const OFFSET_BASE_ADDRESS: usize = HEADER_BYTES + 4;
+const OFFSET_MIN_TICK: usize = HEADER_BYTES + 17;
+
+/// High Precision Event Timer Description Table. See IA-PC HPET Specification 1.0a, 3.2.4.
+#[derive(Debug)]
+pub struct Hpet {
+    base: GenericAddress,
+    num_of_comparators: u8,
+    counter_is_64bit: bool,
+    legacy_replacement: bool,
+    min_tick: u16,
+}
+
+impl Hpet {
+    pub fn new(table: &Table) -> Option<Self> {
+        let id = table.field::<u32>(OFFSET_EVENT_TIMER_BLOCK_ID)?;
+        let base = GenericAddress::parse(table.bytes(), OFFSET_BASE_ADDRESS)?;
+
+        if base.space() != AddressSpace::SystemMemory {
+            warn!("HPET is not memory-mapped: {:?}", base);
+            return None;
+        }
+
+        Some(Self {
+            base,
+            num_of_comparators: u8::try_from((id >> 8) & 0x1f).unwrap() + 1,
+            counter_is_64bit: id & (1 << 13) != 0,
+            legacy_replacement: id & (1 << 15) != 0,
+            min_tick: table.field(OFFSET_MIN_TICK)?,
+        })
+    }
+
+    pub fn base(&self) -> GenericAddress {
+        self.base
+    }
+
+    pub fn num_of_comparators(&self) -> u8 {
+        self.num_of_comparators
+    }
+
+    pub fn counter_is_64bit(&self) -> bool {
+        self.counter_is_64bit
+    }
+
+    pub fn legacy_replacement(&self) -> bool {
+        self.legacy_replacement
+    }
+
+    pub fn min_tick(&self) -> u16 {
+        self.min_tick
+    }
+}
@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::sdt::{self, Table, HEADER_BYTES},
+    alloc::vec::Vec,
+    x86_64::PhysAddr,
+};
+
+const OFFSET_LOCAL_APIC_ADDR: usize = HEADER_BYTES;
+const OFFSET_FLAGS: usize = HEADER_BYTES + 4;
+const OFFSET_ENTRIES: usize = HEADER_BYTES + 8;
+
+const FLAG_PCAT_COMPAT: u32 = 1;
+const LOCAL_APIC_ENABLED: u32 = 1;
+
+const TYPE_LOCAL_APIC: u8 = 0;
+const TYPE_IO_APIC: u8 = 1;
+const TYPE_INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const TYPE_LOCAL_APIC_NMI: u8 = 4;
+const TYPE_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const TYPE_LOCAL_X2APIC: u8 = 9;
+
+/// Multiple APIC Description Table. See ACPI Specification 6.3, 5.2.12.
+#[derive(Debug)]
+pub struct Madt {
+    local_apic_addr: PhysAddr,
+    has_8259: bool,
+    local_apics: Vec<LocalApic>,
+    io_apics: Vec<IoApic>,
+    overrides: Vec<InterruptSourceOverride>,
+    nmis: Vec<LocalApicNmi>,
+}
+
+impl Madt {
+    pub fn new(table: &Table) -> Option<Self> {
+        let mut madt = Self {
+            local_apic_addr: PhysAddr::new(u64::from(table.field::<u32>(OFFSET_LOCAL_APIC_ADDR)?)),
+            has_8259: table.field::<u32>(OFFSET_FLAGS)? & FLAG_PCAT_COMPAT != 0,
+            local_apics: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+            nmis: Vec::new(),
+        };
+
+        let bytes = table.bytes();
+        let mut offset = OFFSET_ENTRIES;
+        while let (Some(ty), Some(len)) = (
+            sdt::read::<u8>(bytes, offset),
+            sdt::read::<u8>(bytes, offset + 1),
+        ) {
+            let len = usize::from(len);
+            if len < 2 || offset + len > bytes.len() {
+                break;
+            }
+
+            madt.parse_entry(ty, &bytes[offset..offset + len]);
+            offset += len;
+        }
+
+        Some(madt)
+    }
+
+    fn parse_entry(&mut self, ty: u8, entry: &[u8]) {
+        match ty {
+            TYPE_LOCAL_APIC => self.local_apics.extend(LocalApic::from_xapic(entry)),
+            TYPE_LOCAL_X2APIC => self.local_apics.extend(LocalApic::from_x2apic(entry)),
+            TYPE_IO_APIC => self.io_apics.extend(IoApic::new(entry)),
+            TYPE_INTERRUPT_SOURCE_OVERRIDE => {
+                self.overrides.extend(InterruptSourceOverride::new(entry))
+            }
+            TYPE_LOCAL_APIC_NMI => self.nmis.extend(LocalApicNmi::new(entry)),
+            TYPE_LOCAL_APIC_ADDRESS_OVERRIDE => {
+                if let Some(addr) = sdt::read::<u64>(entry, 4) {
+                    self.local_apic_addr = PhysAddr::new(addr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn local_apic_addr(&self) -> PhysAddr {
+        self.local_apic_addr
+    }
+
+    /// Whether the system also has the dual 8259 PICs, which must be masked to use I/O APIC.
+    pub fn has_8259(&self) -> bool {
+        self.has_8259
+    }
+
+    pub fn local_apics(&self) -> &[LocalApic] {
+        &self.local_apics
+    }
+
+    pub fn io_apics(&self) -> &[IoApic] {
+        &self.io_apics
+    }
+
+    pub fn overrides(&self) -> &[InterruptSourceOverride] {
+        &self.overrides
+    }
+
+    pub fn nmis(&self) -> &[LocalApicNmi] {
+        &self.nmis
+    }
+
+    /// Returns the override of the ISA IRQ `irq`, if any.
+    pub fn override_of(&self, irq: u8) -> Option<&InterruptSourceOverride> {
+        self.overrides.iter().find(|o| o.source == irq)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LocalApic {
+    processor_uid: u32,
+    apic_id: u32,
+    enabled: bool,
+}
+
+impl LocalApic {
+    fn from_xapic(entry: &[u8]) -> Option<Self> {
+        Some(Self {
+            processor_uid: u32::from(sdt::read::<u8>(entry, 2)?),
+            apic_id: u32::from(sdt::read::<u8>(entry, 3)?),
+            enabled: sdt::read::<u32>(entry, 4)? & LOCAL_APIC_ENABLED != 0,
+        })
+    }
+
+    fn from_x2apic(entry: &[u8]) -> Option<Self> {
+        Some(Self {
+            apic_id: sdt::read(entry, 4)?,
+            enabled: sdt::read::<u32>(entry, 8)? & LOCAL_APIC_ENABLED != 0,
+            processor_uid: sdt::read(entry, 12)?,
+        })
+    }
+
+    pub fn processor_uid(&self) -> u32 {
+        self.processor_uid
+    }
+
+    pub fn apic_id(&self) -> u32 {
+        self.apic_id
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct IoApic {
+    id: u8,
+    addr: PhysAddr,
+    gsi_base: u32,
+}
+
+impl IoApic {
+    fn new(entry: &[u8]) -> Option<Self> {
+        Some(Self {
+            id: sdt::read(entry, 2)?,
+            addr: PhysAddr::new(u64::from(sdt::read::<u32>(entry, 4)?)),
+            gsi_base: sdt::read(entry, 8)?,
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn addr(&self) -> PhysAddr {
+        self.addr
+    }
+
+    pub fn gsi_base(&self) -> u32 {
+        self.gsi_base
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TriggerMode {
+    Edge,
+    Level,
+}
+
+// See ACPI Specification 6.3, Table 5-50. "Conforms to the bus" means active high and edge
+// triggered for ISA.
+fn parse_mps_inti_flags(flags: u16) -> (Polarity, TriggerMode) {
+    let polarity = if flags & 0b11 == 0b11 {
+        Polarity::ActiveLow
+    } else {
+        Polarity::ActiveHigh
+    };
+
+    let trigger = if (flags >> 2) & 0b11 == 0b11 {
+        TriggerMode::Level
+    } else {
+        TriggerMode::Edge
+    };
+
+    (polarity, trigger)
+}
+
+/// Describes how an ISA IRQ is connected to a Global System Interrupt.
+#[derive(Copy, Clone, Debug)]
+pub struct InterruptSourceOverride {
+    source: u8,
+    gsi: u32,
+    polarity: Polarity,
+    trigger: TriggerMode,
+}
+
+impl InterruptSourceOverride {
+    fn new(entry: &[u8]) -> Option<Self> {
+        let (polarity, trigger) = parse_mps_inti_flags(sdt::read(entry, 8)?);
+
+        Some(Self {
+            source: sdt::read(entry, 3)?,
+            gsi: sdt::read(entry, 4)?,
+            polarity,
+            trigger,
+        })
+    }
+
+    pub fn source(&self) -> u8 {
+        self.source
+    }
+
+    pub fn gsi(&self) -> u32 {
+        self.gsi
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    pub fn trigger(&self) -> TriggerMode {
+        self.trigger
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LocalApicNmi {
+    // 0xff means all processors.
+    processor_uid: u8,
+    lint: u8,
+    polarity: Polarity,
+    trigger: TriggerMode,
+}
+
+impl LocalApicNmi {
+    pub const ALL_PROCESSORS: u8 = 0xff;
+
+    fn new(entry: &[u8]) -> Option<Self> {
+        let (polarity, trigger) = parse_mps_inti_flags(sdt::read(entry, 3)?);
+
+        Some(Self {
+            processor_uid: sdt::read(entry, 2)?,
+            lint: sdt::read(entry, 5)?,
+            polarity,
+            trigger,
+        })
+    }
+
+    pub fn processor_uid(&self) -> u8 {
+        self.processor_uid
+    }
+
+    pub fn lint(&self) -> u8 {
+        self.lint
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    pub fn trigger(&self) -> TriggerMode {
+        self.trigger
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::sdt::{self, Table, HEADER_BYTES},
+    alloc::vec::Vec,
+    x86_64::PhysAddr,
+};
+
+// There are 8 reserved bytes after the header.
+const OFFSET_ENTRIES: usize = HEADER_BYTES + 8;
+const BYTES_ENTRY: usize = 16;
+
+/// PCI Express memory mapped configuration space base address Description Table.
+#[derive(Debug)]
+pub struct Mcfg {
+    spaces: Vec<ConfigSpace>,
+}
+
+impl Mcfg {
+    pub fn new(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let spaces = (OFFSET_ENTRIES..bytes.len())
+            .step_by(BYTES_ENTRY)
+            .filter_map(|offset| ConfigSpace::new(bytes, offset))
+            .collect();
+
+        Self { spaces }
+    }
+
+    pub fn spaces(&self) -> &[ConfigSpace] {
+        &self.spaces
+    }
+}
+
+/// The enhanced configuration space of a PCI segment group.
+#[derive(Copy, Clone, Debug)]
+pub struct ConfigSpace {
+    base: PhysAddr,
+    segment: u16,
+    start_bus: u8,
+    end_bus: u8,
+}
+
+impl ConfigSpace {
+    fn new(bytes: &[u8], offset: usize) -> Option<Self> {
+        Some(Self {
+            base: PhysAddr::new(sdt::read(bytes, offset)?),
+            segment: sdt::read(bytes, offset + 8)?,
+            start_bus: sdt::read(bytes, offset + 10)?,
+            end_bus: sdt::read(bytes, offset + 11)?,
+        })
+    }
+
+    pub fn segment(&self) -> u16 {
+        self.segment
+    }
+
+    pub fn buses(&self) -> core::ops::RangeInclusive<u8> {
+        self.start_bus..=self.end_bus
+    }
+
+    /// Returns the physical address of the 4 KiB configuration space of the function.
+    pub fn function_addr(&self, bus: u8, device: u8, function: u8) -> Option<PhysAddr> {
+        if !self.buses().contains(&bus) || device >= 32 || function >= 8 {
+            return None;
+        }
+
+        let offset = (u64::from(bus - self.start_bus) << 20)
+            | (u64::from(device) << 15)
+            | (u64::from(function) << 12);
+
+        Some(self.base + offset)
+    }
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod dsdt;
+pub mod fadt;
+pub mod gas;
+pub mod hpet;
+pub mod madt;
+pub mod mcfg;
+mod rsdp;
+mod sdt;
+
+use {
+    common::kernelboot::{self, record},
+    conquer_once::spin::OnceCell,
+    dsdt::SleepType,
+    fadt::Fadt,
+    hpet::Hpet,
+    madt::Madt,
+    mcfg::Mcfg,
+    rsdp::Rsdp,
+    sdt::{Signature, Table},
+};
+
+static TABLES: OnceCell<Tables> = OnceCell::uninit();
+
+pub fn init(boot_info: &kernelboot::Info) {
+    let addr = match boot_info.record::<record::Rsdp>() {
+        Some(rsdp) => rsdp.addr(),
+        None => {
+            warn!("No RSDP is passed. ACPI is disabled.");
+            return;
+        }
+    };
+
+    let rsdp = match Rsdp::fetch(addr) {
+        Ok(rsdp) => rsdp,
+        Err(e) => {
+            warn!("Invalid RSDP at {:?}: {:?}", addr, e);
+            return;
+        }
+    };
+
+    info!("ACPI revision: {}", rsdp.revision());
+
+    let tables = Tables::fetch(&rsdp);
+    tables.print_summary();
+
+    TABLES
+        .try_init_once(|| tables)
+        .expect("ACPI tables are already initialized.");
+}
+
+pub fn madt() -> Option<&'static Madt> {
+    TABLES.try_get().ok()?.madt.as_ref()
+}
+
+pub fn fadt() -> Option<&'static Fadt> {
+    TABLES.try_get().ok()?.fadt.as_ref()
+}
+
+pub fn hpet() -> Option<&'static Hpet> {
+    TABLES.try_get().ok()?.hpet.as_ref()
+}
+
+pub fn mcfg() -> Option<&'static Mcfg> {
+    TABLES.try_get().ok()?.mcfg.as_ref()
+}
+
+/// The sleep type to power off the system.
+pub fn s5() -> Option<SleepType> {
+    TABLES.try_get().ok()?.s5
+}
+
+#[derive(Default)]
+struct Tables {
+    madt: Option<Madt>,
+    fadt: Option<Fadt>,
+    hpet: Option<Hpet>,
+    mcfg: Option<Mcfg>,
+    s5: Option<SleepType>,
+}
+
+impl Tables {
+    fn fetch(rsdp: &Rsdp) -> Self {
+        let mut tables = Self::default();
+
+        let entries = match sdt::root_entries(rsdp) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to read the root table: {:?}", e);
+                return tables;
+            }
+        };
+
+        for addr in entries {
+            match Table::fetch(addr) {
+                Ok(table) => tables.parse(&table),
+                Err(e) => warn!("Skipping an ACPI table at {:?}: {:?}", addr, e),
+            }
+        }
+
+        if let Some(fadt) = &tables.fadt {
+            tables.s5 = fetch_s5(fadt);
+        }
+
+        tables
+    }
+
+    fn parse(&mut self, table: &Table) {
+        let signature = table.signature();
+        info!("ACPI table: {:?} revision {}", signature, table.revision());
+
+        let parsed = match signature {
+            Signature::MADT => {
+                self.madt = Madt::new(table);
+                self.madt.is_some()
+            }
+            Signature::FADT => {
+                self.fadt = Fadt::new(table);
+                self.fadt.is_some()
+            }
+            Signature::HPET => {
+                self.hpet = Hpet::new(table);
+                self.hpet.is_some()
+            }
+            Signature::MCFG => {
+                self.mcfg = Some(Mcfg::new(table));
+                true
+            }
+            _ => true,
+        };
+
+        if !parsed {
+            warn!("{:?} is malformed.", signature);
+        }
+    }
+
+    fn print_summary(&self) {
+        if let Some(madt) = &self.madt {
+            info!(
+                "Local APIC: {:?} CPUs: {} I/O APICs: {}",
+                madt.local_apic_addr(),
+                madt.local_apics()
+                    .iter()
+                    .filter(|apic| apic.enabled())
+                    .count(),
+                madt.io_apics().len()
+            );
+        }
+
+        if let Some(hpet) = &self.hpet {
+            info!(
+                "HPET: {:X} Comparators: {}",
+                hpet.base().address(),
+                hpet.num_of_comparators()
+            );
+        }
+
+        if let Some(mcfg) = &self.mcfg {
+            for space in mcfg.spaces() {
+                info!(
+                    "PCIe segment {}: buses {:?} at {:?}",
+                    space.segment(),
+                    space.buses(),
+                    space.function_addr(*space.buses().start(), 0, 0)
+                );
+            }
+        }
+    }
+}
+
+fn fetch_s5(fadt: &Fadt) -> Option<SleepType> {
+    let dsdt = match Table::fetch(fadt.dsdt()) {
+        Ok(dsdt) => dsdt,
+        Err(e) => {
+            warn!("Failed to read DSDT: {:?}", e);
+            return None;
+        }
+    };
+
+    let s5 = SleepType::s5(&dsdt);
+    if s5.is_none() {
+        warn!("No _S5_ object is found in DSDT.");
+    }
+
+    s5
+}
+
+fn checksum_is_valid(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b)) == 0
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::checksum_is_valid,
+    crate::mem::allocator::virt,
+    core::{convert::TryFrom, mem::size_of, ptr, slice},
+    os_units::Size,
+    x86_64::PhysAddr,
+};
+
+const SIGNATURE: [u8; 8] = *b"RSD PTR ";
+
+// The bytes covered by the checksum of ACPI 1.0.
+const BYTES_V1: usize = 20;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSignature,
+    InvalidChecksum,
+    InvalidExtendedChecksum,
+}
+
+// See ACPI Specification 6.3, 5.2.5.3.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+#[allow(dead_code)]
+struct Raw {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_addr: u32,
+    length: u32,
+    xsdt_addr: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rsdp {
+    revision: u8,
+    rsdt: PhysAddr,
+    xsdt: Option<PhysAddr>,
+}
+
+impl Rsdp {
+    pub fn fetch(addr: PhysAddr) -> Result<Self, Error> {
+        let bytes = Size::new(size_of::<Raw>());
+        let virt = virt::map(addr, bytes);
+
+        let raw = unsafe { slice::from_raw_parts(virt.as_ptr::<u8>(), size_of::<Raw>()) };
+        let rsdp = Self::parse(raw);
+
+        virt::unmap(virt, bytes);
+
+        rsdp
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: Raw = unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) };
+
+        if raw.signature != SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+
+        if !checksum_is_valid(&bytes[..BYTES_V1]) {
+            return Err(Error::InvalidChecksum);
+        }
+
+        // Revision 0 is ACPI 1.0, which has neither XSDT nor the extended checksum.
+        if raw.revision == 0 {
+            return Ok(Self {
+                revision: raw.revision,
+                rsdt: PhysAddr::new(u64::from(raw.rsdt_addr)),
+                xsdt: None,
+            });
+        }
+
+        let length = usize::try_from(raw.length).unwrap();
+        if length < size_of::<Raw>() || !checksum_is_valid(&bytes[..size_of::<Raw>()]) {
+            return Err(Error::InvalidExtendedChecksum);
+        }
+
+        Ok(Self {
+            revision: raw.revision,
+            rsdt: PhysAddr::new(u64::from(raw.rsdt_addr)),
+            xsdt: Some(PhysAddr::new(raw.xsdt_addr)),
+        })
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn rsdt(&self) -> PhysAddr {
+        self.rsdt
+    }
+
+    pub fn xsdt(&self) -> Option<PhysAddr> {
+        self.xsdt
+    }
+}
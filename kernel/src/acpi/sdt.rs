@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{checksum_is_valid, rsdp::Rsdp},
+    crate::mem::allocator::virt,
+    alloc::vec::Vec,
+    core::{convert::TryFrom, fmt, mem::size_of, ptr, slice, str},
+    os_units::Size,
+    x86_64::PhysAddr,
+};
+
+pub const HEADER_BYTES: usize = 36;
+const OFFSET_LENGTH: usize = 4;
+const OFFSET_REVISION: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Signature([u8; 4]);
+impl Signature {
+    pub const MADT: Self = Self(*b"APIC");
+    pub const FADT: Self = Self(*b"FACP");
+    pub const HPET: Self = Self(*b"HPET");
+    pub const MCFG: Self = Self(*b"MCFG");
+    pub const DSDT: Self = Self(*b"DSDT");
+    const RSDT: Self = Self(*b"RSDT");
+    const XSDT: Self = Self(*b"XSDT");
+}
+impl fmt::Debug for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(str::from_utf8(&self.0).unwrap_or("????"))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TooShort(PhysAddr),
+    InvalidChecksum(Signature),
+    UnexpectedSignature {
+        expected: Signature,
+        found: Signature,
+    },
+}
+
+/// A System Description Table copied onto the heap, so that no mapping is left after parsing.
+pub struct Table {
+    bytes: Vec<u8>,
+}
+
+impl Table {
+    pub fn fetch(addr: PhysAddr) -> Result<Self, Error> {
+        let header = virt::map(addr, Size::new(HEADER_BYTES));
+        let length: u32 =
+            unsafe { ptr::read_unaligned(header.as_ptr::<u8>().add(OFFSET_LENGTH).cast()) };
+        virt::unmap(header, Size::new(HEADER_BYTES));
+
+        let length = usize::try_from(length).unwrap();
+        if length < HEADER_BYTES {
+            return Err(Error::TooShort(addr));
+        }
+
+        let virt = virt::map(addr, Size::new(length));
+        let bytes = unsafe { slice::from_raw_parts(virt.as_ptr::<u8>(), length) }.to_vec();
+        virt::unmap(virt, Size::new(length));
+
+        let table = Self { bytes };
+        if checksum_is_valid(&table.bytes) {
+            Ok(table)
+        } else {
+            Err(Error::InvalidChecksum(table.signature()))
+        }
+    }
+
+    pub fn signature(&self) -> Signature {
+        let mut signature = [0; 4];
+        signature.copy_from_slice(&self.bytes[..4]);
+        Signature(signature)
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.bytes[OFFSET_REVISION]
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Reads a field at `offset` from the beginning of the table. Returns `None` if the table is
+    /// too old to have the field.
+    pub fn field<T: Copy>(&self, offset: usize) -> Option<T> {
+        read(&self.bytes, offset)
+    }
+
+    fn expect_signature(self, expected: Signature) -> Result<Self, Error> {
+        if self.signature() == expected {
+            Ok(self)
+        } else {
+            Err(Error::UnexpectedSignature {
+                expected,
+                found: self.signature(),
+            })
+        }
+    }
+}
+
+/// Returns the addresses of the tables listed in XSDT, or in RSDT if XSDT is not available.
+pub fn root_entries(rsdp: &Rsdp) -> Result<Vec<PhysAddr>, Error> {
+    match rsdp.xsdt() {
+        Some(xsdt) => {
+            let xsdt = Table::fetch(xsdt)?.expect_signature(Signature::XSDT)?;
+            Ok(entries::<u64>(&xsdt).map(PhysAddr::new).collect())
+        }
+        None => {
+            let rsdt = Table::fetch(rsdp.rsdt())?.expect_signature(Signature::RSDT)?;
+            Ok(entries::<u32>(&rsdt)
+                .map(|addr| PhysAddr::new(u64::from(addr)))
+                .collect())
+        }
+    }
+}
+
+fn entries<'a, T: Copy + 'a>(table: &'a Table) -> impl Iterator<Item = T> + 'a {
+    let num_of_entries = (table.bytes().len() - HEADER_BYTES) / size_of::<T>();
+
+    (0..num_of_entries).filter_map(move |i| table.field(HEADER_BYTES + i * size_of::<T>()))
+}
+
+pub fn read<T: Copy>(bytes: &[u8], offset: usize) -> Option<T> {
+    if offset + size_of::<T>() > bytes.len() {
+        return None;
+    }
+
+    Some(unsafe { ptr::read_unaligned(bytes[offset..].as_ptr().cast()) })
+}
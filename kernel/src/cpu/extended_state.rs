@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    alloc::alloc::{self, Layout},
+    conquer_once::spin::OnceCell,
+    core::{arch::x86_64::__cpuid_count, convert::TryFrom, ptr},
+    x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags},
+};
+
+// See Intel SDM Vol. 1, 13.3.
+const XCR0_X87: u64 = 1;
+const XCR0_SSE: u64 = 1 << 1;
+const XCR0_AVX: u64 = 1 << 2;
+
+const LEAF_XSAVE: u32 = 0xd;
+
+// FXSAVE only uses the legacy region of the XSAVE area.
+const BYTES_FXSAVE_AREA: usize = 512;
+const ALIGN_AREA: usize = 64;
+
+// The values after the reset. All the floating-point exceptions are masked.
+const OFFSET_FCW: usize = 0;
+const OFFSET_MXCSR: usize = 24;
+const INITIAL_FCW: u16 = 0x037f;
+const INITIAL_MXCSR: u32 = 0x1f80;
+
+static FORMAT: OnceCell<Format> = OnceCell::uninit();
+
+/// Enables SSE on this CPU, and AVX if the CPU supports it. The bootstrap processor chooses the
+/// state components to save, and the application processors follow it.
+///
+/// Interrupts do not save the extended state. The `x86-interrupt` calling convention saves the SSE
+/// registers a handler uses, and handlers use neither x87 nor AVX.
+pub fn init() {
+    unsafe {
+        Cr0::update(|cr0| {
+            cr0.remove(Cr0Flags::EMULATE_COPROCESSOR);
+            cr0.insert(Cr0Flags::MONITOR_COPROCESSOR | Cr0Flags::NUMERIC_ERROR);
+        });
+        Cr4::update(|cr4| cr4.insert(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE));
+    }
+
+    let format = match FORMAT.try_get() {
+        Ok(format) => *format,
+        Err(_) => {
+            let format = Format::choose();
+            FORMAT
+                .try_init_once(|| format)
+                .expect("The format of the extended state is already chosen.");
+            format
+        }
+    };
+
+    if let Some(xcr0) = format.xcr0 {
+        enable_xsave(xcr0);
+    }
+
+    unsafe { asm!("fninit", options(nomem, nostack)) };
+}
+
+/// The saved extended state of a thread, e.g. the x87, SSE and AVX registers.
+pub struct ExtendedState {
+    area: *mut u8,
+    layout: Layout,
+}
+
+impl ExtendedState {
+    /// The state after the reset.
+    pub fn new() -> Self {
+        let layout = Layout::from_size_align(format().bytes, ALIGN_AREA).unwrap();
+
+        let area = unsafe { alloc::alloc_zeroed(layout) };
+        if area.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        // XRSTOR loads MXCSR even if the SSE state is marked as initial.
+        unsafe {
+            ptr::write(area.add(OFFSET_FCW).cast::<u16>(), INITIAL_FCW);
+            ptr::write(area.add(OFFSET_MXCSR).cast::<u32>(), INITIAL_MXCSR);
+        }
+
+        Self { area, layout }
+    }
+
+    pub fn save(&mut self) {
+        match format().xcr0 {
+            Some(xcr0) => {
+                let (low, high) = split(xcr0);
+                unsafe {
+                    asm!(
+                        "xsave64 [{}]",
+                        in(reg) self.area,
+                        in("eax") low,
+                        in("edx") high,
+                        options(nostack)
+                    );
+                }
+            }
+            None => unsafe { asm!("fxsave64 [{}]", in(reg) self.area, options(nostack)) },
+        }
+    }
+
+    pub fn restore(&self) {
+        match format().xcr0 {
+            Some(xcr0) => {
+                let (low, high) = split(xcr0);
+                unsafe {
+                    asm!(
+                        "xrstor64 [{}]",
+                        in(reg) self.area,
+                        in("eax") low,
+                        in("edx") high,
+                        options(nostack)
+                    );
+                }
+            }
+            None => unsafe { asm!("fxrstor64 [{}]", in(reg) self.area, options(nostack)) },
+        }
+    }
+}
+
+impl Drop for ExtendedState {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.area, self.layout) }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Format {
+    // `None` if FXSAVE is used instead of XSAVE.
+    xcr0: Option<u64>,
+    bytes: usize,
+}
+
+impl Format {
+    fn choose() -> Self {
+        let features = super::features();
+        if !features.xsave() {
+            return Self {
+                xcr0: None,
+                bytes: BYTES_FXSAVE_AREA,
+            };
+        }
+
+        let mut xcr0 = XCR0_X87 | XCR0_SSE;
+        if features.avx() {
+            xcr0 |= XCR0_AVX;
+        }
+        xcr0 &= u64::from(unsafe { __cpuid_count(LEAF_XSAVE, 0) }.eax);
+
+        // CPUID reports the size for the components enabled in XCR0.
+        enable_xsave(xcr0);
+        let bytes = unsafe { __cpuid_count(LEAF_XSAVE, 0) }.ebx;
+
+        Self {
+            xcr0: Some(xcr0),
+            bytes: usize::try_from(bytes).unwrap(),
+        }
+    }
+}
+
+fn format() -> Format {
+    *FORMAT
+        .try_get()
+        .expect("The extended state is not initialized.")
+}
+
+fn enable_xsave(xcr0: u64) {
+    let (low, high) = split(xcr0);
+    unsafe {
+        Cr4::update(|cr4| cr4.insert(Cr4Flags::OSXSAVE));
+        asm!("xsetbv", in("ecx") 0, in("eax") low, in("edx") high, options(nomem, nostack));
+    }
+}
+
+// XSAVE and its family take a 64-bit mask in edx:eax.
+fn split(mask: u64) -> (u32, u32) {
+    (
+        u32::try_from(mask & 0xffff_ffff).unwrap(),
+        u32::try_from(mask >> 32).unwrap(),
+    )
+}
@@ -0,0 +1,177 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod extended_state;
+
+use {
+    crate::smp::percpu,
+    core::{
+        arch::x86_64::{__cpuid, CpuidResult},
+        convert::TryFrom,
+        fmt, str,
+    },
+};
+
+// See Intel SDM Vol. 2A, CPUID.
+const LEAF_VENDOR: u32 = 0;
+const LEAF_FEATURES: u32 = 1;
+const LEAF_EXTENDED_MAX: u32 = 0x8000_0000;
+const LEAF_EXTENDED_FEATURES: u32 = 0x8000_0001;
+
+const ECX_X2APIC: u32 = 1 << 21;
+const ECX_TSC_DEADLINE: u32 = 1 << 24;
+const ECX_XSAVE: u32 = 1 << 26;
+const ECX_AVX: u32 = 1 << 28;
+const ECX_RDRAND: u32 = 1 << 30;
+
+const EXTENDED_EDX_NX: u32 = 1 << 20;
+const EXTENDED_EDX_PAGE_1GIB: u32 = 1 << 26;
+
+/// What the CPU supports, read with CPUID. Each CPU has its own copy in the per-CPU data.
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CpuFeatures {
+    vendor: [u8; 12],
+    family: u32,
+    model: u32,
+    stepping: u32,
+    apic_id: u8,
+    nx: bool,
+    page_1gib: bool,
+    rdrand: bool,
+    tsc_deadline: bool,
+    xsave: bool,
+    avx: bool,
+    x2apic: bool,
+}
+
+impl CpuFeatures {
+    /// Reads the features of the CPU this runs on.
+    pub fn detect() -> Self {
+        let vendor = cpuid(LEAF_VENDOR);
+        let features = cpuid(LEAF_FEATURES);
+
+        let extended = if cpuid(LEAF_EXTENDED_MAX).eax >= LEAF_EXTENDED_FEATURES {
+            cpuid(LEAF_EXTENDED_FEATURES)
+        } else {
+            CpuidResult {
+                eax: 0,
+                ebx: 0,
+                ecx: 0,
+                edx: 0,
+            }
+        };
+
+        let mut vendor_bytes = [0; 12];
+        vendor_bytes[0..4].copy_from_slice(&vendor.ebx.to_le_bytes());
+        vendor_bytes[4..8].copy_from_slice(&vendor.edx.to_le_bytes());
+        vendor_bytes[8..12].copy_from_slice(&vendor.ecx.to_le_bytes());
+
+        let (family, model, stepping) = signature(features.eax);
+
+        Self {
+            vendor: vendor_bytes,
+            family,
+            model,
+            stepping,
+            apic_id: u8::try_from(features.ebx >> 24).unwrap(),
+            nx: extended.edx & EXTENDED_EDX_NX != 0,
+            page_1gib: extended.edx & EXTENDED_EDX_PAGE_1GIB != 0,
+            rdrand: features.ecx & ECX_RDRAND != 0,
+            tsc_deadline: features.ecx & ECX_TSC_DEADLINE != 0,
+            xsave: features.ecx & ECX_XSAVE != 0,
+            avx: features.ecx & ECX_AVX != 0,
+            x2apic: features.ecx & ECX_X2APIC != 0,
+        }
+    }
+
+    /// e.g. "GenuineIntel" or "AuthenticAMD".
+    fn vendor(&self) -> &str {
+        str::from_utf8(&self.vendor).unwrap_or("Unknown")
+    }
+
+    /// The initial APIC ID, which is 8-bit even with x2APIC.
+    pub fn apic_id(&self) -> u8 {
+        self.apic_id
+    }
+
+    pub fn nx(&self) -> bool {
+        self.nx
+    }
+
+    /// Whether the local APIC timer can fire at a TSC value.
+    pub fn tsc_deadline(&self) -> bool {
+        self.tsc_deadline
+    }
+
+    pub fn xsave(&self) -> bool {
+        self.xsave
+    }
+
+    pub fn avx(&self) -> bool {
+        self.avx
+    }
+}
+
+impl fmt::Display for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} family {:#x} model {:#x} stepping {}, APIC ID {}",
+            self.vendor(),
+            self.family,
+            self.model,
+            self.stepping,
+            self.apic_id
+        )?;
+
+        let flags = [
+            (self.nx, "NX"),
+            (self.page_1gib, "1GiB-pages"),
+            (self.rdrand, "RDRAND"),
+            (self.tsc_deadline, "TSC-deadline"),
+            (self.xsave, "XSAVE"),
+            (self.avx, "AVX"),
+            (self.x2apic, "x2APIC"),
+        ];
+
+        let mut separator = ", ";
+        for (_, name) in flags.iter().filter(|(supported, _)| *supported) {
+            write!(f, "{}{}", separator, name)?;
+            separator = " ";
+        }
+
+        Ok(())
+    }
+}
+
+/// The features of the CPU this runs on.
+pub fn features() -> &'static CpuFeatures {
+    percpu::current().features()
+}
+
+// The family and model shown by the vendors combine the base and the extended fields.
+fn signature(eax: u32) -> (u32, u32, u32) {
+    let stepping = eax & 0xf;
+    let base_model = (eax >> 4) & 0xf;
+    let base_family = (eax >> 8) & 0xf;
+    let extended_model = (eax >> 16) & 0xf;
+    let extended_family = (eax >> 20) & 0xff;
+
+    let family = if base_family == 0xf {
+        base_family + extended_family
+    } else {
+        base_family
+    };
+
+    let model = if base_family == 0x6 || base_family == 0xf {
+        (extended_model << 4) + base_model
+    } else {
+        base_model
+    };
+
+    (family, model, stepping)
+}
+
+fn cpuid(leaf: u32) -> CpuidResult {
+    unsafe { __cpuid(leaf) }
+}
@@ -1,7 +1,13 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    crate::graphics::screen::Screen,
+    crate::{
+        graphics::screen::Screen,
+        interrupt::irq,
+        power,
+        sync::Mutex,
+        time::{self, Duration},
+    },
     common::constant::{
         KEY_CMD_MODE, KEY_CMD_WRITE_MODE, KEY_STATUS_SEND_NOT_READY, PORT_KEY_CMD, PORT_KEY_DATA,
         PORT_KEY_STATUS,
@@ -20,10 +26,28 @@ use {
     vek::Vec2,
 };
 
+// Scan code set 1.
+const SCANCODE_CTRL: u8 = 0x1d;
+const SCANCODE_ALT: u8 = 0x38;
+const SCANCODE_END: u8 = 0x4f;
+const SCANCODE_DEL: u8 = 0x53;
+const SCANCODE_RELEASED: u8 = 0x80;
+
+const IRQ: u8 = 1;
+
+const KBC_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const KBC_TIMEOUT: Duration = Duration::from_millis(500);
+
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 static WAKER: AtomicWaker = AtomicWaker::new();
+static KBC: Mutex<()> = Mutex::new(());
+
+fn handle_interrupt() {
+    let mut port = PORT_KEY_DATA;
+    enqueue_scancode(unsafe { port.read() });
+}
 
-pub fn enqueue_scancode(code: u8) {
+fn enqueue_scancode(code: u8) {
     match SCANCODE_QUEUE.try_get() {
         Ok(queue) => {
             if queue.push(code).is_ok() {
@@ -67,12 +91,23 @@ impl Stream for ScancodeStream {
 
 pub async fn task() {
     ScancodeStream::init_queue();
+    irq::register(IRQ, handle_interrupt);
 
-    enable_keyboard();
+    enable_keyboard().await;
 
     let mut scancode_stream = ScancodeStream;
+    let mut modifiers = Modifiers::default();
 
     while let Some(code) = scancode_stream.next().await {
+        modifiers.update(code);
+        if modifiers.ctrl && modifiers.alt {
+            match code {
+                SCANCODE_DEL => power::reboot(),
+                SCANCODE_END => power::shutdown(),
+                _ => {}
+            }
+        }
+
         Screen::draw_rectangle(
             RGB8::new(0, 0x84, 0x84),
             Vec2::new(0, 16),
@@ -82,23 +117,56 @@ pub async fn task() {
     }
 }
 
-fn enable_keyboard() {
-    wait_kbc_sendready();
+#[derive(Default)]
+struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+}
+
+impl Modifiers {
+    fn update(&mut self, code: u8) {
+        let pressed = code & SCANCODE_RELEASED == 0;
+        match code & !SCANCODE_RELEASED {
+            SCANCODE_CTRL => self.ctrl = pressed,
+            SCANCODE_ALT => self.alt = pressed,
+            _ => {}
+        }
+    }
+}
+
+async fn enable_keyboard() {
+    send_command(KEY_CMD_WRITE_MODE, KEY_CMD_MODE).await;
+}
+
+/// Sends `command` and `data` to the keyboard controller. The keyboard and the mouse share the
+/// controller, so the pair is never interleaved with another one.
+pub(super) async fn send_command(command: u8, data: u8) {
+    let _kbc = KBC.lock().await;
+
+    wait_kbc_sendready().await;
 
     let mut port_key_cmd = PORT_KEY_CMD;
-    unsafe { port_key_cmd.write(KEY_CMD_WRITE_MODE as u8) };
+    unsafe { port_key_cmd.write(command) };
 
-    wait_kbc_sendready();
+    wait_kbc_sendready().await;
 
     let mut port_key_data = PORT_KEY_DATA;
-    unsafe { port_key_data.write(KEY_CMD_MODE as u8) };
+    unsafe { port_key_data.write(data) };
 }
 
-pub(super) fn wait_kbc_sendready() {
-    loop {
-        let mut port_key_status = PORT_KEY_STATUS;
-        if unsafe { port_key_status.read() } & KEY_STATUS_SEND_NOT_READY == 0 {
-            break;
+async fn wait_kbc_sendready() {
+    let ready = async {
+        loop {
+            let mut port_key_status = PORT_KEY_STATUS;
+            if unsafe { port_key_status.read() } & KEY_STATUS_SEND_NOT_READY == 0 {
+                break;
+            }
+
+            time::sleep(KBC_POLL_INTERVAL).await;
         }
+    };
+
+    if time::timeout(KBC_TIMEOUT, ready).await.is_err() {
+        warn!("The keyboard controller is not responding.");
     }
 }
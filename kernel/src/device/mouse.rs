@@ -1,8 +1,8 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    crate::graphics::screen::cursor::Cursor,
-    common::constant::{PORT_KEY_CMD, PORT_KEY_DATA},
+    crate::{graphics::screen::cursor::Cursor, interrupt::irq},
+    common::constant::PORT_KEY_DATA,
     conquer_once::spin::OnceCell,
     core::{
         pin::Pin,
@@ -19,12 +19,15 @@ use {
 static MOUSE_PACKET_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 static WAKER: AtomicWaker = AtomicWaker::new();
 
+const IRQ: u8 = 12;
+
 const KEY_CMD_SEND_TO_MOUSE: u8 = 0xD4;
 const MOUSE_CMD_ENABLE: u8 = 0xF4;
 
 pub async fn task() {
     PacketStream::init_queue();
-    Device::enable();
+    irq::register(IRQ, handle_interrupt);
+    Device::enable().await;
     let mut packet_stream = PacketStream;
 
     let mut device = Device::new();
@@ -40,7 +43,12 @@ pub async fn task() {
     }
 }
 
-pub fn enqueue_packet(packet: u8) {
+fn handle_interrupt() {
+    let mut port = PORT_KEY_DATA;
+    enqueue_packet(unsafe { port.read() });
+}
+
+fn enqueue_packet(packet: u8) {
     match MOUSE_PACKET_QUEUE.try_get() {
         Ok(queue) => {
             if queue.push(packet).is_ok() {
@@ -72,16 +80,8 @@ impl Device {
         }
     }
 
-    fn enable() {
-        super::keyboard::wait_kbc_sendready();
-
-        let mut port_key_cmd = PORT_KEY_CMD;
-        unsafe { port_key_cmd.write(KEY_CMD_SEND_TO_MOUSE) };
-
-        super::keyboard::wait_kbc_sendready();
-
-        let mut port_key_data = PORT_KEY_DATA;
-        unsafe { port_key_data.write(MOUSE_CMD_ENABLE) };
+    async fn enable() {
+        super::keyboard::send_command(KEY_CMD_SEND_TO_MOUSE, MOUSE_CMD_ENABLE).await;
     }
 
     fn data_available(&self) -> bool {
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        acpi::{self, fadt::Fadt},
+        time::DateTime,
+    },
+    x86_64::instructions::{interrupts, port::Port},
+};
+
+const PORT_INDEX: u16 = 0x70;
+const PORT_DATA: u16 = 0x71;
+
+// See MC146818A datasheet.
+const REG_SECOND: u8 = 0x00;
+const REG_MINUTE: u8 = 0x02;
+const REG_HOUR: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+const STATUS_B_24_HOUR: u8 = 0x02;
+const STATUS_B_BINARY: u8 = 0x04;
+
+const HOUR_PM: u8 = 0x80;
+
+// Used if FADT does not tell the century register.
+const DEFAULT_CENTURY: u16 = 20;
+
+/// Reads the current date and time, which is assumed to be in UTC.
+pub fn read() -> DateTime {
+    let century = acpi::fadt().and_then(Fadt::century);
+
+    // The registers may be updated while reading them. Read until the same values are read twice.
+    let mut previous = Registers::read(century);
+    loop {
+        let current = Registers::read(century);
+        if current == previous {
+            break;
+        }
+        previous = current;
+    }
+
+    previous.decode(read_register(REG_STATUS_B))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Registers {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    century: Option<u8>,
+}
+
+impl Registers {
+    fn read(century: Option<u8>) -> Self {
+        while read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {}
+
+        Self {
+            second: read_register(REG_SECOND),
+            minute: read_register(REG_MINUTE),
+            hour: read_register(REG_HOUR),
+            day: read_register(REG_DAY),
+            month: read_register(REG_MONTH),
+            year: read_register(REG_YEAR),
+            century: century.map(read_register),
+        }
+    }
+
+    fn decode(self, status_b: u8) -> DateTime {
+        let binary = status_b & STATUS_B_BINARY != 0;
+        let convert = |value: u8| if binary { value } else { from_bcd(value) };
+
+        // The PM flag is the highest bit whichever the format is.
+        let pm = self.hour & HOUR_PM != 0;
+        let mut hour = convert(self.hour & !HOUR_PM);
+        if status_b & STATUS_B_24_HOUR == 0 {
+            hour %= 12;
+            if pm {
+                hour += 12;
+            }
+        }
+
+        let century = self
+            .century
+            .map_or(DEFAULT_CENTURY, |century| u16::from(convert(century)));
+
+        DateTime {
+            year: century * 100 + u16::from(convert(self.year)),
+            month: convert(self.month),
+            day: convert(self.day),
+            hour,
+            minute: convert(self.minute),
+            second: convert(self.second),
+        }
+    }
+}
+
+fn read_register(index: u8) -> u8 {
+    // Selecting a register and reading it must not be interrupted.
+    interrupts::without_interrupts(|| unsafe {
+        Port::new(PORT_INDEX).write(index);
+        Port::new(PORT_DATA).read()
+    })
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
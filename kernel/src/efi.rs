@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::mem::allocator::virt,
+    alloc::vec::Vec,
+    common::kernelboot::{self, record},
+    conquer_once::spin::OnceCell,
+    core::convert::TryFrom,
+    os_units::{NumOfPages, Size},
+    uefi::{
+        table::{
+            boot::{MemoryAttribute, MemoryDescriptor},
+            runtime::ResetType,
+        },
+        Status,
+    },
+    x86_64::{
+        structures::paging::{PageTableFlags, Size4KiB},
+        PhysAddr,
+    },
+};
+
+static RUNTIME: OnceCell<Runtime> = OnceCell::uninit();
+
+struct Runtime {
+    system_table: record::EfiSystemTable,
+    regions: Vec<MemoryDescriptor>,
+}
+
+/// Saves the runtime regions so that the runtime services can be called later.
+///
+/// The memory map is accessed through its physical address, so this function must be called before
+/// the identity mapping is removed.
+pub fn init(boot_info: &mut kernelboot::Info) {
+    let system_table = match boot_info.record::<record::EfiSystemTable>() {
+        Some(table) => table,
+        None => return,
+    };
+
+    let regions = boot_info
+        .mem_map()
+        .iter()
+        .filter(|descriptor| descriptor.att.contains(MemoryAttribute::RUNTIME))
+        .copied()
+        .collect();
+
+    RUNTIME
+        .try_init_once(|| Runtime {
+            system_table,
+            regions,
+        })
+        .expect("EFI runtime services are already initialized.");
+}
+
+/// Calls `ResetSystem`. Returns only if the runtime services are not available.
+pub fn reset(kind: ResetType) {
+    let runtime = match RUNTIME.try_get() {
+        Ok(runtime) => runtime,
+        Err(_) => return,
+    };
+
+    // The bootloader does not call `SetVirtualAddressMap`, so the runtime services expect their
+    // regions to be at the physical addresses.
+    for region in &runtime.regions {
+        let num_of_pages =
+            Size::<NumOfPages<Size4KiB>>::new(usize::try_from(region.page_count).unwrap());
+
+        virt::identity_map(
+            PhysAddr::new(region.phys_start),
+            num_of_pages.as_bytes(),
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        );
+    }
+
+    unsafe {
+        runtime
+            .system_table
+            .get()
+            .runtime_services()
+            .reset(kind, Status::SUCCESS, None)
+    }
+}
@@ -1,41 +1,98 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use crate::x86_64::instructions::segmentation;
+use crate::x86_64::instructions::{segmentation, tables};
+use crate::x86_64::registers::model_specific::GsBase;
 use crate::x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
-use crate::x86_64::PrivilegeLevel;
+use crate::x86_64::structures::tss::TaskStateSegment;
+use crate::x86_64::{PrivilegeLevel, VirtAddr};
+use alloc::{boxed::Box, vec};
 use conquer_once::spin::Lazy;
 
-pub static GDT: Lazy<Gdt> = Lazy::new(|| {
-    let mut gdt = GlobalDescriptorTable::new();
-    let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
+pub const IST_INDEX_DOUBLE_FAULT: u16 = 0;
+pub const IST_INDEX_NMI: u16 = 1;
+pub const IST_INDEX_MACHINE_CHECK: u16 = 2;
 
-    Gdt::new(gdt, code_selector)
-});
+const BYTES_IST_STACK: usize = 4096 * 5;
+
+// These exceptions may happen when the current stack is unusable, e.g. a double fault caused by a
+// stack overflow. Each of them has its own stack.
+static mut DOUBLE_FAULT_STACK: [u8; BYTES_IST_STACK] = [0; BYTES_IST_STACK];
+static mut NMI_STACK: [u8; BYTES_IST_STACK] = [0; BYTES_IST_STACK];
+static mut MACHINE_CHECK_STACK: [u8; BYTES_IST_STACK] = [0; BYTES_IST_STACK];
+
+static TSS: Lazy<TaskStateSegment> =
+    Lazy::new(|| unsafe { new_tss(&DOUBLE_FAULT_STACK, &NMI_STACK, &MACHINE_CHECK_STACK) });
+
+pub static GDT: Lazy<Gdt> = Lazy::new(|| Gdt::new(&TSS));
 
 pub struct Gdt {
     table: GlobalDescriptorTable,
     code_selector: SegmentSelector,
+    tss_selector: SegmentSelector,
 }
 
 impl Gdt {
-    fn new(table: GlobalDescriptorTable, code_selector: SegmentSelector) -> Self {
+    fn new(tss: &'static TaskStateSegment) -> Self {
+        let mut table = GlobalDescriptorTable::new();
+        let code_selector = table.add_entry(Descriptor::kernel_code_segment());
+        let tss_selector = table.add_entry(Descriptor::tss_segment(tss));
+
         Self {
             table,
             code_selector,
+            tss_selector,
+        }
+    }
+
+    fn load(&'static self) {
+        self.table.load();
+
+        // Loading GS may clear its base, which points to the per-CPU data.
+        let gs_base = GsBase::read();
+        unsafe {
+            segmentation::set_cs(self.code_selector);
+
+            let null_seg = SegmentSelector::new(0, PrivilegeLevel::Ring0);
+            segmentation::load_ds(null_seg);
+            segmentation::load_es(null_seg);
+            segmentation::load_fs(null_seg);
+            segmentation::load_gs(null_seg);
+            segmentation::load_ss(null_seg);
+
+            tables::load_tss(self.tss_selector);
+            GsBase::write(gs_base);
         }
     }
 }
 
 pub fn init() {
-    GDT.table.load();
-    unsafe {
-        segmentation::set_cs(GDT.code_selector);
-
-        let null_seg = SegmentSelector::new(0, PrivilegeLevel::Ring0);
-        segmentation::load_ds(null_seg);
-        segmentation::load_es(null_seg);
-        segmentation::load_fs(null_seg);
-        segmentation::load_gs(null_seg);
-        segmentation::load_ss(null_seg);
-    }
+    GDT.load();
+}
+
+/// Loads a GDT and a TSS of an application processor. Each CPU needs its own TSS, as loading a TSS
+/// marks it busy, and its own IST stacks.
+pub fn init_ap() {
+    let new_stack = || &*Box::leak(vec![0_u8; BYTES_IST_STACK].into_boxed_slice());
+    let tss = Box::leak(Box::new(new_tss(new_stack(), new_stack(), new_stack())));
+
+    Box::leak(Box::new(Gdt::new(tss))).load();
+}
+
+fn new_tss(
+    double_fault_stack: &[u8],
+    nmi_stack: &[u8],
+    machine_check_stack: &[u8],
+) -> TaskStateSegment {
+    let mut tss = TaskStateSegment::new();
+    tss.interrupt_stack_table[usize::from(IST_INDEX_DOUBLE_FAULT)] = stack_end(double_fault_stack);
+    tss.interrupt_stack_table[usize::from(IST_INDEX_NMI)] = stack_end(nmi_stack);
+    tss.interrupt_stack_table[usize::from(IST_INDEX_MACHINE_CHECK)] =
+        stack_end(machine_check_stack);
+
+    tss
+}
+
+// The stack grows downwards, so the end of the array is the initial stack pointer.
+fn stack_end(stack: &[u8]) -> VirtAddr {
+    VirtAddr::from_ptr(stack.as_ptr()) + stack.len()
 }
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::layer,
+    crate::{
+        graphics::{font, Vram},
+        time::{self, DateTime, Duration, SystemTime},
+    },
+    futures_util::stream::StreamExt,
+    rgb::RGB8,
+    screen_layer::{self, Layer},
+    vek::Vec2,
+};
+
+// "HH:MM"
+const NUM_OF_CHARS: usize = 5;
+
+const BACKGROUND: RGB8 = RGB8::new(0xc6, 0xc6, 0xc6);
+const FOREGROUND: RGB8 = RGB8::new(0, 0, 0);
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Redraws the clock every second.
+pub async fn task(clock: Clock) {
+    clock.draw(SystemTime::now().into());
+
+    let mut interval = time::interval(UPDATE_INTERVAL);
+    while interval.next().await.is_some() {
+        clock.draw(SystemTime::now().into());
+    }
+}
+
+/// The clock in the right-hand box of the taskbar.
+pub struct Clock {
+    id: screen_layer::Id,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        let layer = Layer::new(
+            Vec2::new(Vram::resolution().x - 45, Vram::resolution().y - 22),
+            Vec2::new(NUM_OF_CHARS * font::FONT_WIDTH, font::FONT_HEIGHT).as_(),
+        );
+
+        let id = layer::get_controller().lock().add_layer(layer);
+
+        Self { id }
+    }
+
+    fn draw(&self, now: DateTime) {
+        let text = [
+            b'0' + now.hour / 10,
+            b'0' + now.hour % 10,
+            b':',
+            b'0' + now.minute / 10,
+            b'0' + now.minute % 10,
+        ];
+
+        layer::get_controller()
+            .lock()
+            .edit_layer(self.id, |layer: &mut Layer| {
+                for (i, c) in text.iter().enumerate() {
+                    for (y, line) in font::FONTS[usize::from(*c)].iter().enumerate() {
+                        for (x, cell) in line.iter().enumerate() {
+                            layer[y][i * font::FONT_WIDTH + x] =
+                                Some(if *cell { FOREGROUND } else { BACKGROUND });
+                        }
+                    }
+                }
+            })
+            .expect("Layer of the clock should be added.");
+    }
+}
@@ -1,12 +1,12 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    super::writer::Writer,
+    super::{writer::Writer, Screen},
+    crate::{graphics::Vram, sync::IrqSpinlock},
     conquer_once::spin::Lazy,
     core::fmt::Write,
     log::{LevelFilter, Metadata, Record, SetLoggerError},
     rgb::RGB8,
-    spinning_top::Spinlock,
     vek::Vec2,
 };
 
@@ -14,8 +14,12 @@ struct Logger;
 
 static LOGGER: Logger = Logger;
 
-static LOG_WRITER: Lazy<Spinlock<Writer>> =
-    Lazy::new(|| Spinlock::new(Writer::new(Vec2::new(0, 100), RGB8::new(0xff, 0xff, 0xff))));
+const COLOR_TEXT: RGB8 = RGB8::new(0xff, 0xff, 0xff);
+const COLOR_CRASH_BACKGROUND: RGB8 = RGB8::new(0, 0, 0x84);
+
+// Interrupt handlers also log.
+static LOG_WRITER: Lazy<IrqSpinlock<Writer>> =
+    Lazy::new(|| IrqSpinlock::new(Writer::new(Vec2::new(0, 100), COLOR_TEXT)));
 
 impl log::Log for Logger {
     fn enabled(&self, _metadata: &Metadata) -> bool {
@@ -32,3 +36,23 @@ impl log::Log for Logger {
 pub fn init() -> Result<(), SetLoggerError> {
     log::set_logger(&LOGGER).map(|()| log::set_max_level(LevelFilter::Info))
 }
+
+/// Clears the screen and moves the log to the top left so that a fatal error is readable.
+///
+/// # Safety
+///
+/// The lock of the log is released forcibly as the faulting code may hold it. The caller must
+/// ensure that nothing else is writing the log.
+pub unsafe fn switch_to_crash_screen() {
+    if LOG_WRITER.is_locked() {
+        LOG_WRITER.force_unlock();
+    }
+
+    Screen::draw_rectangle(
+        COLOR_CRASH_BACKGROUND,
+        Vec2::new(0, 0),
+        *Vram::resolution() - Vec2::new(1, 1),
+    );
+
+    *LOG_WRITER.lock() = Writer::new(Vec2::new(0, 0), COLOR_TEXT);
+}
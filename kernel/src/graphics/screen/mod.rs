@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod clock;
 pub mod cursor;
 pub mod desktop;
 pub mod layer;
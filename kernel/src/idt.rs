@@ -2,18 +2,29 @@
 
 // See P.114
 
-use crate::interrupt;
+use crate::interrupt::{
+    self, exception, irq, VECTOR_LOCAL_TIMER, VECTOR_SPURIOUS, VECTOR_TLB_SHOOTDOWN, VECTOR_WAKEUP,
+};
 use crate::x86_64::structures::idt::InterruptDescriptorTable;
+use crate::{smp, time};
 use conquer_once::spin::Lazy;
+use core::convert::TryFrom;
 
 static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
     let mut idt = InterruptDescriptorTable::new();
-    idt[0x21].set_handler_fn(interrupt::handler_21);
-    idt[0x2c].set_handler_fn(interrupt::handler_2c);
+    exception::set_handlers(&mut idt);
+    for (irq, stub) in irq::STUBS.iter().enumerate() {
+        idt[interrupt::vector(u8::try_from(irq).unwrap())].set_handler_fn(*stub);
+    }
+    idt[usize::from(VECTOR_LOCAL_TIMER)].set_handler_fn(time::handler_local_timer);
+    idt[usize::from(VECTOR_WAKEUP)].set_handler_fn(irq::handler_wakeup);
+    idt[usize::from(VECTOR_TLB_SHOOTDOWN)].set_handler_fn(smp::handler_tlb_shootdown);
+    idt[usize::from(VECTOR_SPURIOUS)].set_handler_fn(irq::handler_spurious);
 
     idt
 });
 
+/// Every CPU shares the same IDT.
 pub fn init() {
     IDT.load();
 }
@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{ioapic::IoApic, VECTOR_IRQ_BASE},
+    crate::{
+        acpi::madt::{self, LocalApicNmi, Madt, Polarity, TriggerMode},
+        mem::allocator::virt,
+    },
+    alloc::vec::Vec,
+    core::{
+        convert::TryFrom,
+        hint, ptr,
+        sync::atomic::{self, Ordering},
+    },
+    os_units::Size,
+    x86_64::{instructions::interrupts, registers::model_specific::Msr, PhysAddr, VirtAddr},
+};
+
+pub const VECTOR_SPURIOUS: u8 = 0xff;
+
+const MSR_APIC_BASE: u32 = 0x1b;
+const MSR_TSC_DEADLINE: u32 = 0x6e0;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+// See Intel SDM Vol. 3A, 10.4.1.
+const OFFSET_ID: u64 = 0x20;
+const OFFSET_TPR: u64 = 0x80;
+const OFFSET_EOI: u64 = 0xb0;
+const OFFSET_SVR: u64 = 0xf0;
+const OFFSET_ICR_LOW: u64 = 0x300;
+const OFFSET_ICR_HIGH: u64 = 0x310;
+const OFFSET_LVT_TIMER: u64 = 0x320;
+const OFFSET_LVT_LINT0: u64 = 0x350;
+const OFFSET_LVT_LINT1: u64 = 0x360;
+const OFFSET_TIMER_INITIAL_COUNT: u64 = 0x380;
+const OFFSET_TIMER_CURRENT_COUNT: u64 = 0x390;
+const OFFSET_TIMER_DIVIDE: u64 = 0x3e0;
+const BYTES_REGISTERS: usize = 0x400;
+
+const SVR_ENABLE: u32 = 1 << 8;
+const LVT_DELIVERY_NMI: u32 = 0b100 << 8;
+const LVT_ACTIVE_LOW: u32 = 1 << 13;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const LVT_TIMER_TSC_DEADLINE: u32 = 0b10 << 17;
+
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+// See Intel SDM Vol. 3A, 10.6.1.
+const ICR_DELIVERY_INIT: u32 = 0b101 << 8;
+const ICR_DELIVERY_STARTUP: u32 = 0b110 << 8;
+const ICR_SEND_PENDING: u32 = 1 << 12;
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+const ICR_ALL_EXCLUDING_SELF: u32 = 0b11 << 18;
+
+/// The local APIC of the bootstrap processor and the I/O APICs.
+pub struct Apic {
+    local: LocalApic,
+    io_apics: Vec<IoApic>,
+    madt: &'static Madt,
+}
+
+impl Apic {
+    /// Returns `None` if the system has no I/O APIC.
+    pub fn new(madt: &'static Madt) -> Option<Self> {
+        if madt.io_apics().is_empty() {
+            return None;
+        }
+
+        let local = LocalApic::new(madt.local_apic_addr());
+        local.enable();
+        local.set_nmis(madt);
+
+        Some(Self {
+            local,
+            io_apics: madt.io_apics().iter().map(IoApic::new).collect(),
+            madt,
+        })
+    }
+
+    /// Routes the ISA IRQ to the vector which the 8259 PIC would use, so that the IDT does not
+    /// depend on the controller.
+    pub fn enable(&self, irq: u8) {
+        let (gsi, polarity, trigger) = match self.madt.override_of(irq) {
+            Some(o) => (o.gsi(), o.polarity(), o.trigger()),
+            None => (u32::from(irq), Polarity::ActiveHigh, TriggerMode::Edge),
+        };
+
+        match self.io_apics.iter().find(|io_apic| io_apic.handles(gsi)) {
+            Some(io_apic) => io_apic.route(
+                gsi,
+                VECTOR_IRQ_BASE + irq,
+                polarity,
+                trigger,
+                self.local.id(),
+            ),
+            None => warn!("No I/O APIC handles GSI {} for IRQ {}.", gsi, irq),
+        }
+    }
+
+    /// Enables the local APIC of the calling application processor. Every local APIC is at the
+    /// same address, so the mapping of the bootstrap processor is reused.
+    pub fn init_ap(&self) {
+        self.local.enable();
+        self.local.set_nmis(self.madt);
+    }
+
+    pub fn local(&self) -> &LocalApic {
+        &self.local
+    }
+
+    pub fn end_of_interrupt(&self) {
+        self.local.end_of_interrupt();
+    }
+}
+
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    fn new(addr: PhysAddr) -> Self {
+        Self {
+            base: virt::map_mmio(addr, Size::new(BYTES_REGISTERS)),
+        }
+    }
+
+    fn enable(&self) {
+        let mut msr = Msr::new(MSR_APIC_BASE);
+        unsafe {
+            let base = msr.read();
+            msr.write(base | APIC_BASE_ENABLE);
+        }
+
+        // Accept all interrupts.
+        self.write(OFFSET_TPR, 0);
+
+        self.write(OFFSET_SVR, SVR_ENABLE | u32::from(VECTOR_SPURIOUS));
+
+        // The firmware may leave LINT0 in the ExtINT mode, which passes the IRQs of the PIC
+        // through. `set_nmis` unmasks the pins connected to NMI.
+        self.write(OFFSET_LVT_LINT0, LVT_MASKED);
+        self.write(OFFSET_LVT_LINT1, LVT_MASKED);
+    }
+
+    fn set_nmis(&self, madt: &Madt) {
+        let uid = madt
+            .local_apics()
+            .iter()
+            .find(|apic| apic.apic_id() == u32::from(self.id()))
+            .map(madt::LocalApic::processor_uid);
+
+        let nmis = madt.nmis().iter().filter(|nmi| {
+            nmi.processor_uid() == LocalApicNmi::ALL_PROCESSORS
+                || Some(u32::from(nmi.processor_uid())) == uid
+        });
+
+        for nmi in nmis {
+            let mut lvt = LVT_DELIVERY_NMI;
+            if nmi.polarity() == Polarity::ActiveLow {
+                lvt |= LVT_ACTIVE_LOW;
+            }
+
+            match nmi.lint() {
+                0 => self.write(OFFSET_LVT_LINT0, lvt),
+                1 => self.write(OFFSET_LVT_LINT1, lvt),
+                n => warn!("Invalid LINT{} for NMI.", n),
+            }
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        u8::try_from(self.read(OFFSET_ID) >> 24).unwrap()
+    }
+
+    pub fn send_init(&self, apic_id: u8) {
+        self.send(apic_id, ICR_DELIVERY_INIT | ICR_LEVEL_ASSERT);
+    }
+
+    /// Makes the processor start in real mode at `page * 0x1000`.
+    pub fn send_startup(&self, apic_id: u8, page: u8) {
+        self.send(
+            apic_id,
+            ICR_DELIVERY_STARTUP | ICR_LEVEL_ASSERT | u32::from(page),
+        );
+    }
+
+    pub fn send_ipi(&self, apic_id: u8, vector: u8) {
+        self.send(apic_id, ICR_LEVEL_ASSERT | u32::from(vector));
+    }
+
+    pub fn send_ipi_to_others(&self, vector: u8) {
+        self.send(
+            0,
+            ICR_ALL_EXCLUDING_SELF | ICR_LEVEL_ASSERT | u32::from(vector),
+        );
+    }
+
+    // An interrupt handler sending another IPI between the two writes would change the
+    // destination.
+    fn send(&self, apic_id: u8, command: u32) {
+        interrupts::without_interrupts(|| {
+            self.write(OFFSET_ICR_HIGH, u32::from(apic_id) << 24);
+            self.write(OFFSET_ICR_LOW, command);
+
+            while self.read(OFFSET_ICR_LOW) & ICR_SEND_PENDING != 0 {
+                hint::spin_loop();
+            }
+        });
+    }
+
+    pub fn end_of_interrupt(&self) {
+        self.write(OFFSET_EOI, 0);
+    }
+
+    /// Starts counting down from the maximum value without raising an interrupt, so that the
+    /// frequency of the timer can be measured with `timer_elapsed`.
+    pub fn start_timer_measurement(&self) {
+        self.write(OFFSET_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(OFFSET_LVT_TIMER, LVT_MASKED);
+        self.write(OFFSET_TIMER_INITIAL_COUNT, u32::MAX);
+    }
+
+    pub fn timer_elapsed(&self) -> u32 {
+        u32::MAX - self.read(OFFSET_TIMER_CURRENT_COUNT)
+    }
+
+    /// Raises `vector` every `count` decrements. The divider is the same as the one used by
+    /// `start_timer_measurement`.
+    pub fn start_periodic_timer(&self, vector: u8, count: u32) {
+        self.write(OFFSET_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(OFFSET_LVT_TIMER, LVT_TIMER_PERIODIC | u32::from(vector));
+        self.write(OFFSET_TIMER_INITIAL_COUNT, count);
+    }
+
+    /// Raises `vector` once the TSC reaches the value set by `set_tsc_deadline`. The CPU must
+    /// support the TSC-deadline mode.
+    pub fn start_tsc_deadline_timer(&self, vector: u8) {
+        self.write(OFFSET_LVT_TIMER, LVT_TIMER_TSC_DEADLINE | u32::from(vector));
+
+        // The mode must be set before the deadline. See Intel SDM Vol. 3A, 10.5.4.1.
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    /// Arms the timer started by `start_tsc_deadline_timer`. It fires only once.
+    pub fn set_tsc_deadline(&self, tsc: u64) {
+        unsafe { Msr::new(MSR_TSC_DEADLINE).write(tsc) };
+    }
+
+    fn read(&self, offset: u64) -> u32 {
+        unsafe { ptr::read_volatile((self.base + offset).as_ptr()) }
+    }
+
+    fn write(&self, offset: u64, value: u32) {
+        unsafe { ptr::write_volatile((self.base + offset).as_mut_ptr(), value) }
+    }
+}
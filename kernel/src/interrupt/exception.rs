@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{gdt, graphics::screen::log, multitask::thread},
+    common::constant::STACK_GUARD_PAGE,
+    core::mem,
+    x86_64::{
+        registers::control::Cr2,
+        structures::{
+            idt::{InterruptDescriptorTable, InterruptStackFrameValue},
+            paging::{PageSize, Size4KiB},
+        },
+    },
+};
+
+const VECTOR_DOUBLE_FAULT: u64 = 8;
+const VECTOR_PAGE_FAULT: u64 = 14;
+
+type Stub = unsafe extern "C" fn() -> !;
+
+/// The state of the CPU when an exception occurred, in the order `common_stub` pushes it.
+#[repr(C)]
+struct Frame {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    r11: u64,
+    r10: u64,
+    r9: u64,
+    r8: u64,
+    rbp: u64,
+    rdi: u64,
+    rsi: u64,
+    rdx: u64,
+    rcx: u64,
+    rbx: u64,
+    rax: u64,
+    vector: u64,
+    error_code: u64,
+    stack_frame: InterruptStackFrameValue,
+}
+
+// `x86-interrupt` functions cannot read the general registers, so each stub pushes the vector and
+// jumps to `common_stub`, which saves the registers. Exceptions without an error code push 0 to
+// keep `Frame` in the same layout.
+macro_rules! without_error_code {
+    ($($name:ident => $vector:expr),* $(,)?) => {$(
+        #[naked]
+        unsafe extern "C" fn $name() -> ! {
+            asm!("push 0", "push {}", "jmp {}", const $vector, sym common_stub, options(noreturn));
+        }
+    )*};
+}
+
+macro_rules! with_error_code {
+    ($($name:ident => $vector:expr),* $(,)?) => {$(
+        #[naked]
+        unsafe extern "C" fn $name() -> ! {
+            asm!("push {}", "jmp {}", const $vector, sym common_stub, options(noreturn));
+        }
+    )*};
+}
+
+without_error_code! {
+    stub_divide_error => 0,
+    stub_debug => 1,
+    stub_non_maskable_interrupt => 2,
+    stub_breakpoint => 3,
+    stub_overflow => 4,
+    stub_bound_range_exceeded => 5,
+    stub_invalid_opcode => 6,
+    stub_device_not_available => 7,
+    stub_x87_floating_point => 16,
+    stub_machine_check => 18,
+    stub_simd_floating_point => 19,
+    stub_virtualization => 20,
+}
+
+with_error_code! {
+    stub_double_fault => 8,
+    stub_invalid_tss => 10,
+    stub_segment_not_present => 11,
+    stub_stack_segment_fault => 12,
+    stub_general_protection_fault => 13,
+    stub_page_fault => 14,
+    stub_alignment_check => 17,
+    stub_security_exception => 30,
+}
+
+pub fn set_handlers(idt: &mut InterruptDescriptorTable) {
+    unsafe {
+        idt.divide_error.set_handler_fn(handler(stub_divide_error));
+        idt.debug.set_handler_fn(handler(stub_debug));
+        idt.non_maskable_interrupt
+            .set_handler_fn(handler(stub_non_maskable_interrupt))
+            .set_stack_index(gdt::IST_INDEX_NMI);
+        idt.breakpoint.set_handler_fn(handler(stub_breakpoint));
+        idt.overflow.set_handler_fn(handler(stub_overflow));
+        idt.bound_range_exceeded
+            .set_handler_fn(handler(stub_bound_range_exceeded));
+        idt.invalid_opcode
+            .set_handler_fn(handler(stub_invalid_opcode));
+        idt.device_not_available
+            .set_handler_fn(handler(stub_device_not_available));
+        idt.double_fault
+            .set_handler_fn(handler(stub_double_fault))
+            .set_stack_index(gdt::IST_INDEX_DOUBLE_FAULT);
+        idt.invalid_tss.set_handler_fn(handler(stub_invalid_tss));
+        idt.segment_not_present
+            .set_handler_fn(handler(stub_segment_not_present));
+        idt.stack_segment_fault
+            .set_handler_fn(handler(stub_stack_segment_fault));
+        idt.general_protection_fault
+            .set_handler_fn(handler(stub_general_protection_fault));
+        idt.page_fault.set_handler_fn(handler(stub_page_fault));
+        idt.x87_floating_point
+            .set_handler_fn(handler(stub_x87_floating_point));
+        idt.alignment_check
+            .set_handler_fn(handler(stub_alignment_check));
+        idt.machine_check
+            .set_handler_fn(handler(stub_machine_check))
+            .set_stack_index(gdt::IST_INDEX_MACHINE_CHECK);
+        idt.simd_floating_point
+            .set_handler_fn(handler(stub_simd_floating_point));
+        idt.virtualization
+            .set_handler_fn(handler(stub_virtualization));
+        idt.security_exception
+            .set_handler_fn(handler(stub_security_exception));
+    }
+}
+
+// The IDT only accepts `x86-interrupt` functions, whose addresses are what the CPU needs.
+unsafe fn handler<F>(stub: Stub) -> F {
+    mem::transmute_copy(&stub)
+}
+
+// The CPU aligns the stack to 16 bytes before pushing the stack frame. The frame has 5 entries,
+// and 17 more are pushed here, so the stack is aligned when calling `report`.
+#[naked]
+unsafe extern "C" fn common_stub() -> ! {
+    asm!(
+        "push rax",
+        "push rbx",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push rbp",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "cld",
+        "mov rdi, rsp",
+        "call {}",
+        sym report,
+        options(noreturn)
+    );
+}
+
+extern "C" fn report(frame: &Frame) -> ! {
+    unsafe { log::switch_to_crash_screen() };
+
+    error!(
+        "EXCEPTION: {} (vector {})",
+        name(frame.vector),
+        frame.vector
+    );
+    error!("Error code: {:#X}", frame.error_code);
+    if frame.vector == VECTOR_PAGE_FAULT || frame.vector == VECTOR_DOUBLE_FAULT {
+        error!("CR2: {:#X}", Cr2::read().as_u64());
+    }
+
+    let stack_frame = &frame.stack_frame;
+    error!(
+        "RIP: {:#X} CS: {:#X} RFLAGS: {:#X}",
+        stack_frame.instruction_pointer.as_u64(),
+        stack_frame.code_segment,
+        stack_frame.cpu_flags
+    );
+    error!(
+        "RSP: {:#X} SS: {:#X}",
+        stack_frame.stack_pointer.as_u64(),
+        stack_frame.stack_segment
+    );
+
+    error!(
+        "RAX: {:016X} RBX: {:016X} RCX: {:016X}",
+        frame.rax, frame.rbx, frame.rcx
+    );
+    error!(
+        "RDX: {:016X} RSI: {:016X} RDI: {:016X}",
+        frame.rdx, frame.rsi, frame.rdi
+    );
+    error!(
+        "RBP: {:016X} R8:  {:016X} R9:  {:016X}",
+        frame.rbp, frame.r8, frame.r9
+    );
+    error!(
+        "R10: {:016X} R11: {:016X} R12: {:016X}",
+        frame.r10, frame.r11, frame.r12
+    );
+    error!(
+        "R13: {:016X} R14: {:016X} R15: {:016X}",
+        frame.r13, frame.r14, frame.r15
+    );
+
+    if is_stack_overflow(frame) {
+        panic!("Kernel stack overflow");
+    }
+
+    panic!("Unhandled exception: {}", name(frame.vector));
+}
+
+// Touching the guard page causes a page fault, but pushing its stack frame touches the guard page
+// again, which results in a double fault. CR2 still holds the address in the guard page, either of
+// the boot stack or of the stack of the current thread.
+fn is_stack_overflow(frame: &Frame) -> bool {
+    let cr2 = Cr2::read();
+    (frame.vector == VECTOR_PAGE_FAULT || frame.vector == VECTOR_DOUBLE_FAULT)
+        && ((STACK_GUARD_PAGE..STACK_GUARD_PAGE + Size4KiB::SIZE).contains(&cr2)
+            || thread::is_in_stack_guard_page(cr2))
+}
+
+fn name(vector: u64) -> &'static str {
+    match vector {
+        0 => "Divide Error",
+        1 => "Debug",
+        2 => "Non-maskable Interrupt",
+        3 => "Breakpoint",
+        4 => "Overflow",
+        5 => "BOUND Range Exceeded",
+        6 => "Invalid Opcode",
+        7 => "Device Not Available",
+        8 => "Double Fault",
+        10 => "Invalid TSS",
+        11 => "Segment Not Present",
+        12 => "Stack-Segment Fault",
+        13 => "General Protection Fault",
+        14 => "Page Fault",
+        16 => "x87 Floating-Point Exception",
+        17 => "Alignment Check",
+        18 => "Machine Check",
+        19 => "SIMD Floating-Point Exception",
+        20 => "Virtualization Exception",
+        30 => "Security Exception",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        acpi::madt::{self, Polarity, TriggerMode},
+        mem::allocator::virt,
+    },
+    core::ptr,
+    os_units::Size,
+    spinning_top::Spinlock,
+    x86_64::VirtAddr,
+};
+
+// See 82093AA I/O Advanced Programmable Interrupt Controller (IOAPIC) datasheet.
+const OFFSET_IOREGSEL: u64 = 0x00;
+const OFFSET_IOWIN: u64 = 0x10;
+const BYTES_REGISTERS: usize = 0x20;
+
+const REG_VERSION: u32 = 0x01;
+const REG_REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+const REDIRECTION_DESTINATION_SHIFT: u64 = 56;
+
+pub struct IoApic {
+    // The lock keeps a pair of selecting a register and accessing it from being interleaved.
+    base: Spinlock<VirtAddr>,
+    gsi_base: u32,
+    num_of_entries: u32,
+}
+
+impl IoApic {
+    pub fn new(io_apic: &madt::IoApic) -> Self {
+        let base = virt::map_mmio(io_apic.addr(), Size::new(BYTES_REGISTERS));
+
+        let mut io_apic = Self {
+            base: Spinlock::new(base),
+            gsi_base: io_apic.gsi_base(),
+            num_of_entries: 0,
+        };
+
+        io_apic.num_of_entries = ((io_apic.read(REG_VERSION) >> 16) & 0xff) + 1;
+        io_apic.mask_all();
+
+        io_apic
+    }
+
+    pub fn handles(&self, gsi: u32) -> bool {
+        (self.gsi_base..self.gsi_base + self.num_of_entries).contains(&gsi)
+    }
+
+    /// Delivers the interrupt from `gsi` to the local APIC `apic_id` as `vector`.
+    pub fn route(
+        &self,
+        gsi: u32,
+        vector: u8,
+        polarity: Polarity,
+        trigger: TriggerMode,
+        apic_id: u8,
+    ) {
+        assert!(
+            self.handles(gsi),
+            "GSI {} is not handled by this I/O APIC.",
+            gsi
+        );
+
+        let mut entry = u64::from(vector) | (u64::from(apic_id) << REDIRECTION_DESTINATION_SHIFT);
+        if polarity == Polarity::ActiveLow {
+            entry |= REDIRECTION_ACTIVE_LOW;
+        }
+        if trigger == TriggerMode::Level {
+            entry |= REDIRECTION_LEVEL_TRIGGERED;
+        }
+
+        self.write_entry(gsi - self.gsi_base, entry);
+    }
+
+    fn mask_all(&self) {
+        for i in 0..self.num_of_entries {
+            self.write_entry(i, REDIRECTION_MASKED);
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_entry(&self, index: u32, entry: u64) {
+        let reg = REG_REDIRECTION_TABLE + index * 2;
+
+        // Mask the entry first so that no interrupt is delivered with a half-written entry.
+        self.write(reg, REDIRECTION_MASKED as u32);
+        self.write(reg + 1, (entry >> 32) as u32);
+        self.write(reg, entry as u32);
+    }
+
+    fn read(&self, reg: u32) -> u32 {
+        let base = self.base.lock();
+        unsafe {
+            ptr::write_volatile((*base + OFFSET_IOREGSEL).as_mut_ptr(), reg);
+            ptr::read_volatile((*base + OFFSET_IOWIN).as_ptr())
+        }
+    }
+
+    fn write(&self, reg: u32, value: u32) {
+        let base = self.base.lock();
+        unsafe {
+            ptr::write_volatile((*base + OFFSET_IOREGSEL).as_mut_ptr(), reg);
+            ptr::write_volatile((*base + OFFSET_IOWIN).as_mut_ptr(), value);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::controller,
+    crate::{multitask::thread, sync::IrqSpinlock},
+    core::sync::atomic::{AtomicU64, Ordering},
+    x86_64::structures::idt::{HandlerFunc, InterruptStackFrame},
+};
+
+pub const NUM_OF_IRQS: usize = 16;
+
+/// Called with interrupts disabled. It must not wait for anything which needs an interrupt.
+pub type Handler = fn();
+
+static LINES: IrqSpinlock<[Line; NUM_OF_IRQS]> = IrqSpinlock::new([Line::new(); NUM_OF_IRQS]);
+static NUM_OF_SPURIOUS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets `handler` for `irq` and enables the line. The interrupt controller is responsible for EOI,
+/// so `handler` only needs to deal with the device.
+///
+/// # Panics
+///
+/// This function panics if `irq` is out of range or already has a handler.
+pub fn register(irq: u8, handler: Handler) {
+    assert!(usize::from(irq) < NUM_OF_IRQS, "Invalid IRQ: {}", irq);
+
+    {
+        let mut lines = LINES.lock();
+        let line = &mut lines[usize::from(irq)];
+        assert!(line.handler.is_none(), "IRQ {} is already registered.", irq);
+
+        line.handler = Some(handler);
+    }
+
+    controller().enable(irq);
+}
+
+// There is no way for a handler to know its vector, so a stub is generated for each line.
+macro_rules! stubs {
+    ($($name:ident => $irq:expr),* $(,)?) => {{
+        $(
+            extern "x86-interrupt" fn $name(_stack_frame: &mut InterruptStackFrame) {
+                dispatch($irq);
+            }
+        )*
+
+        [$($name),*]
+    }};
+}
+
+/// The handlers of IRQ 0 to 15, in order.
+pub const STUBS: [HandlerFunc; NUM_OF_IRQS] = stubs![
+    stub_0 => 0,
+    stub_1 => 1,
+    stub_2 => 2,
+    stub_3 => 3,
+    stub_4 => 4,
+    stub_5 => 5,
+    stub_6 => 6,
+    stub_7 => 7,
+    stub_8 => 8,
+    stub_9 => 9,
+    stub_10 => 10,
+    stub_11 => 11,
+    stub_12 => 12,
+    stub_13 => 13,
+    stub_14 => 14,
+    stub_15 => 15,
+];
+
+// The local APIC does not expect EOI for a spurious interrupt.
+pub extern "x86-interrupt" fn handler_spurious(_stack_frame: &mut InterruptStackFrame) {
+    count_spurious(None);
+}
+
+/// Handles the IPI sent by `interrupt::wake_up`. The interrupt itself ends `hlt`, so there is
+/// nothing else to do.
+pub extern "x86-interrupt" fn handler_wakeup(_stack_frame: &mut InterruptStackFrame) {
+    if let Some(local_apic) = super::local_apic() {
+        local_apic.end_of_interrupt();
+    }
+
+    thread::preempt_if_requested();
+}
+
+fn dispatch(irq: u8) {
+    if controller().is_spurious(irq) {
+        count_spurious(Some(irq));
+        return;
+    }
+
+    let handler = {
+        let mut lines = LINES.lock();
+        let line = &mut lines[usize::from(irq)];
+        if line.handler.is_none() {
+            line.num_of_unhandled += 1;
+            if line.num_of_unhandled.is_power_of_two() {
+                warn!(
+                    "IRQ {} has no handler. ({} times)",
+                    irq, line.num_of_unhandled
+                );
+            }
+        }
+        line.handler
+    };
+
+    if let Some(handler) = handler {
+        handler();
+    }
+
+    controller().end_of_interrupt(irq);
+
+    thread::preempt_if_requested();
+}
+
+// Only powers of two are logged so that an interrupt storm does not flood the screen.
+fn count_spurious(irq: Option<u8>) {
+    let n = NUM_OF_SPURIOUS.fetch_add(1, Ordering::Relaxed) + 1;
+    if n.is_power_of_two() {
+        match irq {
+            Some(irq) => warn!("Spurious IRQ {}. ({} spurious interrupts)", irq, n),
+            None => warn!("Spurious interrupt. ({} spurious interrupts)", n),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Line {
+    handler: Option<Handler>,
+    num_of_unhandled: u64,
+}
+
+impl Line {
+    const fn new() -> Self {
+        Self {
+            handler: None,
+            num_of_unhandled: 0,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+mod apic;
+pub mod exception;
+mod ioapic;
+pub mod irq;
+mod pic;
+
+use {crate::acpi, apic::Apic, conquer_once::spin::OnceCell};
+
+pub use apic::{LocalApic, VECTOR_SPURIOUS};
+
+/// The vector of IRQ 0. IRQ `n` is delivered as `VECTOR_IRQ_BASE + n` whichever controller is used.
+pub const VECTOR_IRQ_BASE: u8 = 0x20;
+
+pub const VECTOR_LOCAL_TIMER: u8 = 0x40;
+
+/// The IPI which only wakes up a halted CPU.
+pub const VECTOR_WAKEUP: u8 = 0x41;
+
+/// The IPI which makes a CPU flush its TLB.
+pub const VECTOR_TLB_SHOOTDOWN: u8 = 0x42;
+
+static CONTROLLER: OnceCell<Controller> = OnceCell::uninit();
+
+enum Controller {
+    Pic,
+    Apic(Apic),
+}
+
+impl Controller {
+    fn enable(&self, irq: u8) {
+        match self {
+            Self::Pic => pic::enable(irq),
+            Self::Apic(apic) => apic.enable(irq),
+        }
+    }
+
+    fn end_of_interrupt(&self, irq: u8) {
+        match self {
+            Self::Pic => pic::end_of_interrupt(irq),
+            Self::Apic(apic) => apic.end_of_interrupt(),
+        }
+    }
+
+    // The local APIC delivers spurious interrupts to `VECTOR_SPURIOUS` instead.
+    fn is_spurious(&self, irq: u8) -> bool {
+        match self {
+            Self::Pic => pic::is_spurious(irq),
+            Self::Apic(_) => false,
+        }
+    }
+}
+
+/// Masks all interrupts from the 8259 PIC. No interrupt is delivered until `init_controller` is
+/// called and a handler is registered.
+pub fn init() {
+    pic::init();
+}
+
+/// Selects the APIC if ACPI reports one, or the 8259 PIC otherwise. ACPI must be initialized
+/// before calling this.
+pub fn init_controller() {
+    let controller = match acpi::madt().and_then(|madt| Some((madt, Apic::new(madt)?))) {
+        Some((madt, apic)) => {
+            // Otherwise a legacy IRQ would also arrive through the PIC, and never be acknowledged.
+            if madt.has_8259() {
+                pic::disable();
+            }
+
+            info!("Interrupt controller: APIC");
+            Controller::Apic(apic)
+        }
+        None => {
+            info!("Interrupt controller: 8259 PIC");
+            Controller::Pic
+        }
+    };
+
+    CONTROLLER
+        .try_init_once(|| controller)
+        .expect("The interrupt controller is already initialized.");
+}
+
+/// Enables the local APIC of an application processor. Application processors are started only
+/// if the APIC is used.
+pub fn init_ap() {
+    match controller() {
+        Controller::Apic(apic) => apic.init_ap(),
+        Controller::Pic => unreachable!("An application processor is running without APIC."),
+    }
+}
+
+/// Wakes up the CPU with `apic_id` if it is halted. Nothing is sent to this CPU itself, as it is
+/// obviously awake.
+pub fn wake_up(apic_id: u8) {
+    if let Ok(Controller::Apic(apic)) = CONTROLLER.try_get() {
+        if apic.local().id() != apic_id {
+            apic.local().send_ipi(apic_id, VECTOR_WAKEUP);
+        }
+    }
+}
+
+/// Sends `vector` to every running CPU except this one. Nothing is sent if the 8259 PIC is used,
+/// as only this CPU runs then.
+pub fn send_ipi_to_others(vector: u8) {
+    if let Ok(Controller::Apic(apic)) = CONTROLLER.try_get() {
+        apic.local().send_ipi_to_others(vector);
+    }
+}
+
+/// The local APIC of this CPU. `None` if the 8259 PIC is used.
+pub fn local_apic() -> Option<&'static LocalApic> {
+    match controller() {
+        Controller::Pic => None,
+        Controller::Apic(apic) => Some(apic.local()),
+    }
+}
+
+pub fn vector(irq: u8) -> usize {
+    usize::from(VECTOR_IRQ_BASE + irq)
+}
+
+fn controller() -> &'static Controller {
+    CONTROLLER
+        .try_get()
+        .expect("The interrupt controller is not initialized.")
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {super::VECTOR_IRQ_BASE, x86_64::instructions::port::Port};
+
+const PIC0_ICW1: u16 = 0x0020;
+const PIC0_OCW2: u16 = 0x0020;
+const PIC0_OCW3: u16 = 0x0020;
+const PIC0_IMR: u16 = 0x0021;
+const PIC0_ICW2: u16 = 0x0021;
+const PIC0_ICW3: u16 = 0x0021;
+const PIC0_ICW4: u16 = 0x0021;
+const PIC1_ICW1: u16 = 0x00A0;
+const PIC1_OCW2: u16 = 0x00A0;
+const PIC1_OCW3: u16 = 0x00A0;
+const PIC1_IMR: u16 = 0x00A1;
+const PIC1_ICW2: u16 = 0x00A1;
+const PIC1_ICW3: u16 = 0x00A1;
+const PIC1_ICW4: u16 = 0x00A1;
+
+const IRQ_CASCADE: u8 = 2;
+const NUM_OF_IRQS_PER_PIC: u8 = 8;
+
+const OCW2_SPECIFIC_EOI: u8 = 0x60;
+const OCW3_READ_ISR: u8 = 0x0b;
+
+// The lowest priority line of each PIC, which is raised when an IRQ disappears before the CPU
+// acknowledges it.
+const IRQ_SPURIOUS_MASTER: u8 = 7;
+const IRQ_SPURIOUS_SLAVE: u8 = 15;
+
+// See P.128. ICW1 clears IMR, so the IRQs are masked after the initialization.
+pub fn init() {
+    enable_edge_trigger_mode();
+    set_irq_receiver();
+    set_connection();
+    enable_nonbuffer_mode();
+    disable_all_interrupts();
+}
+
+/// Masks every line of both PICs, including the cascade. Used when the APIC takes over.
+pub fn disable() {
+    unsafe {
+        Port::new(PIC0_IMR).write(0xFF as u8);
+        Port::new(PIC1_IMR).write(0xFF as u8);
+    }
+}
+
+pub fn enable(irq: u8) {
+    if irq < NUM_OF_IRQS_PER_PIC {
+        unmask(PIC0_IMR, irq);
+    } else {
+        unmask(PIC1_IMR, irq - NUM_OF_IRQS_PER_PIC);
+        unmask(PIC0_IMR, IRQ_CASCADE);
+    }
+}
+
+pub fn end_of_interrupt(irq: u8) {
+    unsafe {
+        if irq < NUM_OF_IRQS_PER_PIC {
+            Port::new(PIC0_OCW2).write(OCW2_SPECIFIC_EOI | irq);
+        } else {
+            Port::new(PIC1_OCW2).write(OCW2_SPECIFIC_EOI | (irq - NUM_OF_IRQS_PER_PIC));
+            Port::new(PIC0_OCW2).write(OCW2_SPECIFIC_EOI | IRQ_CASCADE);
+        }
+    }
+}
+
+/// Returns `true` if `irq` is not actually in service. A spurious IRQ must not be acknowledged, but
+/// this function sends EOI to the master for a spurious IRQ from the slave as the master does not
+/// know it is spurious.
+pub fn is_spurious(irq: u8) -> bool {
+    match irq {
+        IRQ_SPURIOUS_MASTER => !in_service(PIC0_OCW3, IRQ_SPURIOUS_MASTER),
+        IRQ_SPURIOUS_SLAVE => {
+            let spurious = !in_service(PIC1_OCW3, IRQ_SPURIOUS_SLAVE - NUM_OF_IRQS_PER_PIC);
+            if spurious {
+                unsafe { Port::new(PIC0_OCW2).write(OCW2_SPECIFIC_EOI | IRQ_CASCADE) };
+            }
+            spurious
+        }
+        _ => false,
+    }
+}
+
+fn in_service(ocw3: u16, line: u8) -> bool {
+    let mut port = Port::<u8>::new(ocw3);
+    unsafe {
+        port.write(OCW3_READ_ISR);
+        port.read() & (1 << line) != 0
+    }
+}
+
+/// Masks every IRQ. The cascade stays unmasked, but it does not matter as all lines of the slave
+/// are masked.
+fn disable_all_interrupts() {
+    unsafe {
+        Port::new(PIC0_IMR).write(!(1_u8 << IRQ_CASCADE));
+        Port::new(PIC1_IMR).write(0xFF as u8);
+    }
+}
+
+fn unmask(imr: u16, line: u8) {
+    let mut port = Port::<u8>::new(imr);
+    unsafe {
+        let mask = port.read();
+        port.write(mask & !(1 << line));
+    }
+}
+
+fn enable_edge_trigger_mode() {
+    unsafe {
+        Port::new(PIC0_ICW1).write(0x11 as u8);
+        Port::new(PIC1_ICW1).write(0x11 as u8);
+    }
+}
+
+fn set_irq_receiver() {
+    unsafe {
+        Port::new(PIC0_ICW2).write(VECTOR_IRQ_BASE);
+        Port::new(PIC1_ICW2).write(VECTOR_IRQ_BASE + NUM_OF_IRQS_PER_PIC);
+    }
+}
+
+fn set_connection() {
+    unsafe {
+        Port::new(PIC0_ICW3).write(1_u8 << IRQ_CASCADE);
+        Port::new(PIC1_ICW3).write(IRQ_CASCADE);
+    }
+}
+
+fn enable_nonbuffer_mode() {
+    unsafe {
+        Port::new(PIC0_ICW4).write(0x01 as u8);
+        Port::new(PIC1_ICW4).write(0x01 as u8);
+    }
+}
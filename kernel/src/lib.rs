@@ -6,6 +6,7 @@
 #![feature(const_fn)]
 #![feature(wake_trait)]
 #![feature(asm)]
+#![feature(global_asm)]
 #![feature(panic_info_message)]
 #![feature(start)]
 #![feature(naked_functions)]
@@ -21,29 +22,51 @@ extern crate alloc;
 extern crate log;
 extern crate x86_64;
 
+mod acpi;
+mod cpu;
 mod device;
+mod efi;
 mod gdt;
 mod idt;
 mod interrupt;
 mod mem;
 mod multitask;
 mod panic;
+mod power;
+#[cfg(feature = "qemu_test")]
+mod qemu_test;
+mod smp;
+mod sync;
+mod time;
 
 #[macro_use]
 mod graphics;
 
 use {
-    common::kernelboot,
-    device::{keyboard, mouse},
+    common::kernelboot::{self, record},
+    device::{keyboard, mouse, rtc},
     graphics::{
-        screen::{self, desktop::Desktop, layer},
+        screen::{
+            self,
+            clock::{self, Clock},
+            desktop::Desktop,
+            layer,
+        },
         Vram,
     },
     mem::{
-        allocator::{heap, phys::FrameManager},
-        paging,
+        allocator::{
+            heap,
+            phys::{self, FrameManager},
+            slab,
+        },
+        map, paging,
+    },
+    multitask::{
+        executor::Executor,
+        task::Task,
+        thread::{self, Priority},
     },
-    multitask::{executor::Executor, task::Task},
 };
 
 #[no_mangle]
@@ -55,46 +78,107 @@ pub extern "win64" fn os_main(mut boot_info: kernelboot::Info) -> ! {
 }
 
 fn initialization(boot_info: &mut kernelboot::Info) {
+    // Even the framebuffer cannot be read from the boot information before it is validated.
+    if let Err(e) = boot_info.validate() {
+        halt_on_incompatible_bootloader(boot_info, &e);
+    }
+
     Vram::init(&boot_info);
+    screen::log::init().unwrap();
 
     gdt::init();
     idt::init();
-    interrupt::init_pic();
+    interrupt::init();
 
     FrameManager::init(boot_info.mem_map());
 
+    smp::percpu::init_early();
     heap::init();
 
+    smp::percpu::init(0);
+    cpu::extended_state::init();
+    thread::init();
+
     layer::init();
 
-    screen::log::init().unwrap();
+    efi::init(boot_info);
 
+    // The memory map is read through the identity mapping, which is removed here.
+    let mem_map = boot_info.mem_map().to_vec();
     paging::mark_pages_as_unused();
+    paging::unmap_stack_guard_page();
 
     let desktop = Desktop::new();
     desktop.draw();
 
     info!("Hello Ramen OS!");
     info!("Vram information: {}", Vram::display());
+    info!("CPU 0: {}", cpu::features());
+
+    map::print(&mem_map);
+
+    acpi::init(&boot_info);
+
+    if let Some(cmdline) = boot_info.record::<record::CommandLine>() {
+        info!("Command line: {}", cmdline.as_str());
+    }
+
+    // The memory map and the other buffers of the bootloader are freed here, so nothing may read
+    // them after this.
+    FrameManager::reclaim_boot_memory(boot_info, &mem_map);
+    info!("{}", phys::stats());
+    info!("{}", heap::stats());
+    info!("{}", slab::stats());
+
+    interrupt::init_controller();
+
+    time::init();
+
+    let now = rtc::read();
+    time::set_wall_clock(now);
+    info!("Date: {}", now);
+
+    smp::init();
+}
 
-    interrupt::set_init_pic_bits();
+// The error is shown only if the framebuffer is usable. Otherwise there is nothing to show it on.
+fn halt_on_incompatible_bootloader(boot_info: &kernelboot::Info, e: &kernelboot::Error) -> ! {
+    if let kernelboot::Error::MissingRecord(_) = e {
+        if boot_info.record::<common::vram::Info>().is_some() {
+            Vram::init(boot_info);
+            screen::log::init().unwrap();
+            panic!("The bootloader is incompatible with this kernel: {:?}", e);
+        }
+    }
+
+    loop {
+        x86_64::instructions::hlt();
+    }
 }
 
 #[cfg(not(feature = "qemu_test"))]
 fn run_tasks() -> ! {
-    let mut executor = Executor::new();
-    executor.spawn(Task::new(keyboard::task()));
-    executor.spawn(Task::new(mouse::task()));
-    executor.run();
+    // Add the clock layer before the cursor one so that the cursor is drawn over the clock.
+    let clock = Clock::new();
+
+    // Each executor runs in its own thread so that a busy task in one does not block the input.
+    thread::spawn(Priority::High, || {
+        let mut executor = Executor::new();
+        executor.spawn(Task::named("keyboard", keyboard::task()));
+        executor.spawn(Task::named("mouse", mouse::task()));
+        executor.run();
+    });
+
+    thread::spawn(Priority::Low, move || {
+        let mut executor = Executor::new();
+        executor.spawn(Task::named("clock", clock::task(clock)));
+        executor.run();
+    });
+
+    thread::exit();
 }
 
 #[cfg(feature = "qemu_test")]
 fn run_tasks() -> ! {
-    // Currently there is no way to test multitasking. If this OS suppports timer, the situation
-    // may change.
-    //
-    // If you change the value `0xf4` and `33`, don't forget to change the correspond values in
-    // `Makefile`!
-    use qemu_exit::QEMUExit;
-    qemu_exit::X86::new(0xf4, 33).exit_success();
+    qemu_test::run();
 }
@@ -1,53 +1,213 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-// WORKAROUND: https://stackoverflow.com/questions/63933070/clippy-says-too-many-arguments-to-static-declaration
-#![allow(clippy::too_many_arguments)]
-
 use {
-    super::{super::paging::pml4::PML4, phys::FRAME_MANAGER},
+    super::{
+        super::paging::pml4::PML4,
+        phys::{self, Consumer, Zone, FRAME_MANAGER},
+        slab,
+    },
+    crate::sync::IrqSpinlock,
     common::constant::{BYTES_KERNEL_HEAP, KERNEL_HEAP_ADDR},
-    core::{alloc::Layout, convert::TryFrom},
-    linked_list_allocator::LockedHeap,
+    core::{
+        alloc::Layout,
+        convert::TryFrom,
+        fmt,
+        ptr::{self, NonNull},
+    },
+    linked_list_allocator::Heap,
     x86_64::structures::paging::{
-        FrameAllocator, Mapper, Page, PageSize, PageTableFlags, Size4KiB,
+        mapper::MapToError, Mapper, Page, PageSize, PageTableFlags, Size4KiB,
     },
 };
 
-#[global_allocator]
-pub static ALLOCATOR: LockedHeap = LockedHeap::empty();
+// The heap starts with this and grows by at least this much at a time, up to `BYTES_KERNEL_HEAP`.
+const BYTES_HEAP_CHUNK: usize = 0x10_0000;
 
-// Using UEFI's `allocate_pages` doesn't work for allocating larger memory. It returns out of
-// resrouces.
-pub fn init() {
-    for i in 0..BYTES_KERNEL_HEAP.as_num_of_pages::<Size4KiB>().as_usize() {
-        let frame = FRAME_MANAGER
-            .lock()
-            .allocate_frame()
-            .expect("OOM during initializing heap memory.");
-        let page =
-            Page::<Size4KiB>::containing_address(KERNEL_HEAP_ADDR + Size4KiB::SIZE * i as u64);
-        unsafe {
-            PML4.lock()
-                .map_to(
-                    page,
-                    frame,
-                    PageTableFlags::PRESENT,
-                    &mut *FRAME_MANAGER.lock(),
-                )
-                .unwrap()
-                .flush();
-        };
+// Small objects are allocated from the slabs in front of this.
+static ALLOCATOR: Allocator = Allocator(IrqSpinlock::new(Inner {
+    heap: Heap::empty(),
+    stats: Stats {
+        mapped: 0,
+        used: 0,
+        peak: 0,
+        allocations: 0,
+    },
+}));
+
+// A thread may be preempted while holding the heap lock, and then another thread which allocates
+// with interrupts disabled would spin forever. `IrqSpinlock` prevents such preemption.
+struct Allocator(IrqSpinlock<Inner>);
+
+impl Allocator {
+    fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut inner = self.0.lock();
+
+        loop {
+            if let Ok(ptr) = inner.heap.allocate_first_fit(layout) {
+                inner.stats.allocated(layout.size());
+                return ptr.as_ptr();
+            }
+
+            if !inner.grow(layout) {
+                return ptr::null_mut();
+            }
+        }
     }
 
-    unsafe {
-        ALLOCATOR.lock().init(
-            usize::try_from(KERNEL_HEAP_ADDR.as_u64()).unwrap(),
-            BYTES_KERNEL_HEAP.as_usize(),
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut inner = self.0.lock();
+        inner.heap.deallocate(NonNull::new_unchecked(ptr), layout);
+        inner.stats.deallocated(layout.size());
+    }
+}
+
+struct Inner {
+    heap: Heap,
+    stats: Stats,
+}
+
+impl Inner {
+    // Maps more pages at the end of the heap. Returns `false` if nothing is mapped.
+    fn grow(&mut self, layout: Layout) -> bool {
+        let limit = BYTES_KERNEL_HEAP.as_usize() - self.stats.mapped;
+
+        // The new memory may be separate from the free block at the end of the heap, so it must be
+        // large enough on its own.
+        let bytes = (layout.size() + layout.align())
+            .max(BYTES_HEAP_CHUNK)
+            .min(limit);
+        let bytes = round_up_to_page(bytes);
+
+        let mapped = map_pages(self.stats.mapped, bytes);
+        if mapped == 0 {
+            return false;
+        }
+
+        unsafe { self.heap.extend(mapped) };
+        self.stats.mapped += mapped;
+        true
+    }
+}
+
+/// The amount of the kernel heap, in bytes.
+#[derive(Copy, Clone)]
+pub struct Stats {
+    mapped: usize,
+    used: usize,
+    peak: usize,
+    allocations: usize,
+}
+
+impl Stats {
+    fn allocated(&mut self, bytes: usize) {
+        self.used += bytes;
+        self.peak = self.peak.max(self.used);
+        self.allocations += 1;
+    }
+
+    fn deallocated(&mut self, bytes: usize) {
+        self.used -= bytes;
+        self.allocations -= 1;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Heap: mapped {} KiB of {} KiB, used {} KiB (peak {} KiB) by {} allocations",
+            self.mapped / 1024,
+            BYTES_KERNEL_HEAP.as_usize() / 1024,
+            self.used / 1024,
+            self.peak / 1024,
+            self.allocations
         )
     }
 }
 
+/// Maps the first part of the heap. The rest is mapped when needed.
+pub fn init() {
+    let mut inner = ALLOCATOR.0.lock();
+
+    let mapped = map_pages(0, BYTES_HEAP_CHUNK);
+    assert!(mapped > 0, "OOM during initializing heap memory.");
+
+    unsafe {
+        inner
+            .heap
+            .init(usize::try_from(KERNEL_HEAP_ADDR.as_u64()).unwrap(), mapped)
+    }
+    inner.stats.mapped = mapped;
+}
+
+/// Allocates from the heap directly. Returns null if the heap cannot grow anymore.
+pub fn alloc(layout: Layout) -> *mut u8 {
+    ALLOCATOR.alloc(layout)
+}
+
+/// # Safety
+///
+/// `ptr` must be allocated by `alloc` with `layout`.
+pub unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
+    ALLOCATOR.dealloc(ptr, layout)
+}
+
+/// A snapshot of the statistics.
+pub fn stats() -> Stats {
+    ALLOCATOR.0.lock().stats
+}
+
+// Maps pages from `offset` bytes after the start of the heap. Returns the number of bytes mapped,
+// which is less than `bytes` if the frames run out.
+fn map_pages(offset: usize, bytes: usize) -> usize {
+    let start = KERNEL_HEAP_ADDR + offset;
+    let num_of_pages = bytes / usize::try_from(Size4KiB::SIZE).unwrap();
+
+    for i in 0..num_of_pages {
+        let frame = FRAME_MANAGER.lock().allocate(0, Zone::Any, Consumer::Heap);
+        let frame = match frame {
+            Some(frame) => frame,
+            None => return i * usize::try_from(Size4KiB::SIZE).unwrap(),
+        };
+
+        let page = Page::<Size4KiB>::containing_address(start + Size4KiB::SIZE * i as u64);
+        let result = unsafe {
+            PML4.lock().map_to(
+                page,
+                frame,
+                PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
+                &mut *FRAME_MANAGER.lock(),
+            )
+        };
+
+        match result {
+            Ok(flush) => flush.flush(),
+            // No frame for a page table.
+            Err(MapToError::FrameAllocationFailed) => {
+                unsafe { FRAME_MANAGER.lock().deallocate(frame, 0, Consumer::Heap) };
+                return i * usize::try_from(Size4KiB::SIZE).unwrap();
+            }
+            Err(e) => panic!("Failed to map the heap: {:?}", e),
+        }
+    }
+
+    bytes
+}
+
+fn round_up_to_page(bytes: usize) -> usize {
+    let page = usize::try_from(Size4KiB::SIZE).unwrap();
+    (bytes + page - 1) / page * page
+}
+
 #[alloc_error_handler]
 fn alloc_error(layout: Layout) -> ! {
-    panic!("Allocation failed! {:?}", layout);
+    error!(
+        "Out of memory: {} bytes aligned to {} bytes are requested.",
+        layout.size(),
+        layout.align()
+    );
+    error!("{}", stats());
+    error!("{}", slab::stats());
+    error!("{}", phys::stats());
+    panic!("Out of memory.");
 }
@@ -2,4 +2,6 @@
 
 pub mod heap;
 pub mod phys;
+pub mod slab;
+pub mod stack;
 pub mod virt;
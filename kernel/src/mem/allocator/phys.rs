@@ -1,28 +1,134 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    common::constant::{CHANGE_FREE_PAGE_ADDR, FREE_PAGE_ADDR},
+    crate::sync::IrqSpinlock,
+    alloc::vec::Vec,
+    common::{
+        constant::{CHANGE_FREE_PAGE_ADDR, FREE_PAGE_ADDR},
+        kernelboot::{self, record},
+    },
     conquer_once::spin::Lazy,
-    core::ptr,
-    spinning_top::Spinlock,
+    core::{fmt, iter, ptr},
     uefi::table::boot::{self, MemoryType},
     x86_64::{
         instructions::tlb,
-        structures::paging::{FrameAllocator, FrameDeallocator, PageSize, PhysFrame, Size4KiB},
+        registers::control::Cr3,
+        structures::paging::{
+            FrameAllocator, FrameDeallocator, PageSize, PageTableFlags, PhysFrame, Size4KiB,
+        },
         PhysAddr,
     },
 };
 
-pub static FRAME_MANAGER: Lazy<Spinlock<FrameManager>> = Lazy::new(|| {
-    Spinlock::new(FrameManager {
-        head: None,
-        tail: None,
-    })
-});
+// A block of order `n` is `2^n` contiguous frames aligned to its size. The largest block covers
+// the 52-bit physical address space.
+const NUM_OF_ORDERS: usize = 40;
+
+const NUM_OF_ZONES: usize = 3;
+const LIMIT_ZONE_1MIB: u64 = 0x10_0000;
+const LIMIT_ZONE_4GIB: u64 = 0x1_0000_0000;
+
+// Adjacent ranges are merged, so this is far more than firmware reports.
+const MAX_NUM_OF_RANGES: usize = 128;
+
+// The types in the UEFI specification. The others are vendor-specific.
+const KNOWN_TYPES: [MemoryType; 15] = [
+    MemoryType::RESERVED,
+    MemoryType::LOADER_CODE,
+    MemoryType::LOADER_DATA,
+    MemoryType::BOOT_SERVICES_CODE,
+    MemoryType::BOOT_SERVICES_DATA,
+    MemoryType::RUNTIME_SERVICES_CODE,
+    MemoryType::RUNTIME_SERVICES_DATA,
+    MemoryType::CONVENTIONAL,
+    MemoryType::UNUSABLE,
+    MemoryType::ACPI_RECLAIM,
+    MemoryType::ACPI_NON_VOLATILE,
+    MemoryType::MMIO,
+    MemoryType::MMIO_PORT_SPACE,
+    MemoryType::PAL_CODE,
+    MemoryType::PERSISTENT_MEMORY,
+];
+
+const CONSUMERS: [Consumer; 5] = [
+    Consumer::Heap,
+    Consumer::PageTables,
+    Consumer::Dma,
+    Consumer::Stacks,
+    Consumer::Other,
+];
+
+// The memory which nothing uses once the kernel has copied what it needs from the boot
+// information.
+const RECLAIMABLE_TYPES: [MemoryType; 3] = [
+    MemoryType::BOOT_SERVICES_CODE,
+    MemoryType::BOOT_SERVICES_DATA,
+    MemoryType::LOADER_DATA,
+];
+
+// Marks the header of a free block. It is XORed with the address of the block, so that a header
+// copied to another frame is not taken for a free block.
+const MAGIC_FREE: u64 = 0x6672_6565_626c_6f63;
+
+// The heap grows with interrupts disabled, so this must not be held by a preempted thread.
+pub static FRAME_MANAGER: Lazy<IrqSpinlock<FrameManager>> =
+    Lazy::new(|| IrqSpinlock::new(FrameManager::new()));
+
+/// A snapshot of the statistics.
+pub fn stats() -> Stats {
+    FRAME_MANAGER.lock().stats
+}
+
+/// Where an allocated block must be.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Zone {
+    /// e.g. for the startup code of the application processors.
+    Below1MiB,
+    /// e.g. for devices which only take 32-bit addresses. No driver needs it yet.
+    #[allow(dead_code)]
+    Below4GiB,
+    Any,
+}
+
+/// What allocated frames are used for. Only used for the statistics.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Consumer {
+    Heap,
+    PageTables,
+    /// No driver needs it yet.
+    #[allow(dead_code)]
+    Dma,
+    Stacks,
+    Other,
+}
+
+impl Consumer {
+    fn index(self) -> usize {
+        CONSUMERS.iter().position(|&c| c == self).unwrap()
+    }
+}
+
+impl Zone {
+    // The indices of the zones to search, from the one whose memory is the least scarce.
+    fn candidates(self) -> &'static [usize] {
+        match self {
+            Self::Below1MiB => &[0],
+            Self::Below4GiB => &[1, 0],
+            Self::Any => &[2, 1, 0],
+        }
+    }
+}
 
+/// A buddy allocator. Each free block has a header linking it to the other free blocks of the same
+/// order and zone. Blocks never span the zones, whose limits are aligned to large powers of two.
 pub struct FrameManager {
-    head: Option<PhysAddr>,
-    tail: Option<PhysAddr>,
+    // The first free block of each order in each zone.
+    heads: [[Option<PhysAddr>; NUM_OF_ORDERS]; NUM_OF_ZONES],
+    // The memory handed to the allocator. A header is read only from these ranges, as reading
+    // other memory may have side effects.
+    ranges: [Range; MAX_NUM_OF_RANGES],
+    num_of_ranges: usize,
+    stats: Stats,
 }
 
 impl FrameManager {
@@ -30,46 +136,268 @@ impl FrameManager {
         FRAME_MANAGER.lock().init_static(mem_map);
     }
 
+    /// Frees the memory used by the firmware and the bootloader, except the ranges in
+    /// `reserved::Map`, the loaded modules and the PML4. `mem_map` must be a copy, as the memory
+    /// map itself is freed.
+    pub fn reclaim_boot_memory(boot_info: &kernelboot::Info, mem_map: &[boot::MemoryDescriptor]) {
+        let mut excluded: Vec<(u64, u64)> = boot_info
+            .reserved()
+            .iter()
+            .map(|range| (range.phys().as_u64(), range.bytes().as_usize() as u64))
+            .chain(
+                boot_info
+                    .records::<record::Module>()
+                    .map(|module| (module.start().as_u64(), module.bytes().as_usize() as u64)),
+            )
+            .chain(iter::once((
+                Cr3::read().0.start_address().as_u64(),
+                Size4KiB::SIZE,
+            )))
+            .map(|(start, bytes)| (align_down(start), align_up(start + bytes)))
+            .collect();
+        excluded.sort_unstable();
+
+        let mut reclaimed = [0; RECLAIMABLE_TYPES.len()];
+        let mut manager = FRAME_MANAGER.lock();
+        for descriptor in mem_map
+            .iter()
+            .filter(|descriptor| RECLAIMABLE_TYPES.contains(&descriptor.ty))
+        {
+            let start = descriptor.phys_start;
+            let end = start + Size4KiB::SIZE * descriptor.page_count;
+            let index = RECLAIMABLE_TYPES
+                .iter()
+                .position(|&ty| ty == descriptor.ty)
+                .unwrap();
+
+            for_each_uncovered(start, end, &excluded, |start, end| {
+                reclaimed[index] +=
+                    manager.add_range(PhysAddr::new(start), PhysAddr::new(end), descriptor.ty);
+            });
+        }
+        drop(manager);
+
+        for (ty, bytes) in RECLAIMABLE_TYPES.iter().zip(&reclaimed) {
+            info!("Reclaimed {:?}: {} KiB", ty, bytes / 1024);
+        }
+    }
+
+    /// Allocates `2^order` contiguous frames in `zone`, aligned to their size.
+    pub fn allocate(&mut self, order: usize, zone: Zone, consumer: Consumer) -> Option<PhysFrame> {
+        for &zone in zone.candidates() {
+            if let Some(found) = (order..NUM_OF_ORDERS).find(|&i| self.heads[zone][i].is_some()) {
+                let addr = self.heads[zone][found].unwrap();
+                self.remove(addr, zone, found);
+
+                // Return the upper halves.
+                for i in (order..found).rev() {
+                    self.push(addr + (Size4KiB::SIZE << i), zone, i);
+                }
+
+                self.account(addr, order, consumer, true);
+                return Some(PhysFrame::containing_address(addr));
+            }
+        }
+
+        None
+    }
+
+    /// # Safety
+    ///
+    /// `frame` must be the start of a block allocated with `order` for `consumer`, which is no
+    /// longer used.
+    pub unsafe fn deallocate(&mut self, frame: PhysFrame, order: usize, consumer: Consumer) {
+        self.account(frame.start_address(), order, consumer, false);
+        self.free(frame.start_address(), order);
+    }
+
+    fn new() -> Self {
+        Self {
+            heads: [[None; NUM_OF_ORDERS]; NUM_OF_ZONES],
+            ranges: [Range {
+                start: 0,
+                end: 0,
+                ty: MemoryType::RESERVED,
+            }; MAX_NUM_OF_RANGES],
+            num_of_ranges: 0,
+            stats: Stats::new(),
+        }
+    }
+
     fn init_static(&mut self, mem_map: &[boot::MemoryDescriptor]) {
         for descriptor in mem_map {
+            let bytes = Size4KiB::SIZE * descriptor.page_count;
+            self.stats.reported[type_index(descriptor.ty)] += bytes;
+
             if Self::available(descriptor.ty) {
-                self.init_for_descriptor(descriptor);
+                let start = PhysAddr::new(descriptor.phys_start);
+                self.add_range(start, start + bytes, descriptor.ty);
+            }
+        }
+    }
+
+    // Moves the bytes of the block between free and used, for each type of the memory it spans.
+    fn account(&mut self, addr: PhysAddr, order: usize, consumer: Consumer, allocated: bool) {
+        let (start, end) = (addr.as_u64(), addr.as_u64() + (Size4KiB::SIZE << order));
+        let stats = &mut self.stats;
+
+        for range in &self.ranges[..self.num_of_ranges] {
+            let bytes = range.end.min(end).saturating_sub(range.start.max(start));
+            let free = &mut stats.free[type_index(range.ty)];
+            if allocated {
+                *free -= bytes;
+            } else {
+                *free += bytes;
             }
         }
+
+        let used = &mut stats.used_by[consumer.index()];
+        if allocated {
+            *used += end - start;
+        } else {
+            *used -= end - start;
+        }
+    }
+
+    // The range is split into as few blocks as possible, so this takes time proportional to the
+    // number of ranges, not frames. Returns the number of bytes added.
+    fn add_range(&mut self, start: PhysAddr, end: PhysAddr, ty: MemoryType) -> u64 {
+        // The null frame is skipped so that a null pointer never points to a mapped page.
+        let start = start.max(PhysAddr::new(Size4KiB::SIZE));
+        if start >= end {
+            return 0;
+        }
+
+        if !self.record_range(start, end, ty) {
+            warn!(
+                "Too many memory ranges. {:?}..{:?} is not used.",
+                start, end
+            );
+            return 0;
+        }
+
+        let mut addr = start;
+        while addr < end {
+            let order = largest_order(addr, end);
+            self.free(addr, order);
+            addr += Size4KiB::SIZE << order;
+        }
+
+        let bytes = end - start;
+        self.stats.managed[type_index(ty)] += bytes;
+        self.stats.free[type_index(ty)] += bytes;
+        bytes
     }
 
-    fn init_for_descriptor(&mut self, descriptor: &boot::MemoryDescriptor) {
-        for i in 0..descriptor.page_count {
-            let addr = PhysAddr::new(descriptor.phys_start + Size4KiB::SIZE * i);
-            if addr.is_null() {
-                continue;
+    // Adjacent ranges are merged if they have the same type.
+    fn record_range(&mut self, start: PhysAddr, end: PhysAddr, ty: MemoryType) -> bool {
+        let (start, end) = (start.as_u64(), end.as_u64());
+        let ranges = &mut self.ranges[..self.num_of_ranges];
+
+        let before = ranges
+            .iter()
+            .position(|range| range.end == start && range.ty == ty);
+        let after = ranges
+            .iter()
+            .position(|range| range.start == end && range.ty == ty);
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                // The range fills the gap between two ranges.
+                ranges[before].end = ranges[after].end;
+                ranges[after] = ranges[self.num_of_ranges - 1];
+                self.num_of_ranges -= 1;
             }
+            (Some(before), None) => ranges[before].end = end,
+            (None, Some(after)) => ranges[after].start = start,
+            (None, None) => {
+                if self.num_of_ranges == MAX_NUM_OF_RANGES {
+                    return false;
+                }
 
-            if self.head.is_none() {
-                self.head = Some(addr);
+                self.ranges[self.num_of_ranges] = Range { start, end, ty };
+                self.num_of_ranges += 1;
             }
+        }
+
+        true
+    }
 
-            unsafe {
-                ptr::write(addr.as_u64() as *mut Option<PhysAddr>, None);
+    // The block may span several ranges of different types.
+    fn manages(&self, start: PhysAddr, bytes: u64) -> bool {
+        let (mut addr, end) = (start.as_u64(), start.as_u64() + bytes);
+        while addr < end {
+            match self.ranges[..self.num_of_ranges]
+                .iter()
+                .find(|range| range.start <= addr && addr < range.end)
+            {
+                Some(range) => addr = range.end,
+                None => return false,
             }
+        }
+
+        true
+    }
+
+    // Merges the block with its buddy as long as the buddy is free.
+    fn free(&mut self, mut addr: PhysAddr, mut order: usize) {
+        let zone = zone_of(addr);
 
-            if let Some(prev) = self.tail {
-                unsafe { ptr::write(prev.as_u64() as _, Some(addr)) }
+        while order + 1 < NUM_OF_ORDERS {
+            let bytes = Size4KiB::SIZE << order;
+            let buddy = PhysAddr::new(addr.as_u64() ^ bytes);
+
+            if zone_of(buddy) != zone
+                || !self.manages(buddy, bytes)
+                || !Self::is_free_block(buddy, order)
+            {
+                break;
             }
 
-            self.tail = Some(addr);
+            self.remove(buddy, zone, order);
+            addr = PhysAddr::new(addr.as_u64() & !bytes);
+            order += 1;
         }
+
+        self.push(addr, zone, order);
     }
 
-    fn change_free_page_ptr(addr: PhysAddr) {
-        const PAGE_EXISTS: u64 = 1;
-        unsafe {
-            ptr::write(
-                CHANGE_FREE_PAGE_ADDR.as_mut_ptr(),
-                addr.as_u64() | PAGE_EXISTS,
-            )
+    fn push(&mut self, addr: PhysAddr, zone: usize, order: usize) {
+        let next = self.heads[zone][order];
+        write_header(
+            addr,
+            Header {
+                magic: MAGIC_FREE ^ addr.as_u64(),
+                order,
+                prev: None,
+                next,
+            },
+        );
+
+        if let Some(next) = next {
+            update_header(next, |header| header.prev = Some(addr));
+        }
+
+        self.heads[zone][order] = Some(addr);
+    }
+
+    fn remove(&mut self, addr: PhysAddr, zone: usize, order: usize) {
+        let header = read_header(addr);
+
+        match header.prev {
+            Some(prev) => update_header(prev, |prev| prev.next = header.next),
+            None => self.heads[zone][order] = header.next,
         }
-        tlb::flush(CHANGE_FREE_PAGE_ADDR);
+
+        if let Some(next) = header.next {
+            update_header(next, |next| next.prev = header.prev);
+        }
+
+        update_header(addr, |header| header.magic = 0);
+    }
+
+    fn is_free_block(addr: PhysAddr, order: usize) -> bool {
+        let header = read_header(addr);
+        header.magic == MAGIC_FREE ^ addr.as_u64() && header.order == order
     }
 
     fn available(ty: boot::MemoryType) -> bool {
@@ -77,27 +405,228 @@ impl FrameManager {
     }
 }
 
+// The mapper allocates frames only for page tables.
 unsafe impl FrameAllocator<Size4KiB> for FrameManager {
     fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
-        match self.head {
-            None => None,
-            Some(addr) => {
-                Self::change_free_page_ptr(addr);
-                unsafe {
-                    self.head = ptr::read(addr.as_u64() as _);
-                }
+        self.allocate(0, Zone::Any, Consumer::PageTables)
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for FrameManager {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        self.deallocate(frame, 0, Consumer::PageTables);
+    }
+}
+
+/// The amount of the physical memory, in bytes.
+#[derive(Copy, Clone)]
+pub struct Stats {
+    // Indexed like `KNOWN_TYPES`. The last one is for the vendor-specific types.
+    reported: [u64; KNOWN_TYPES.len() + 1],
+    managed: [u64; KNOWN_TYPES.len() + 1],
+    free: [u64; KNOWN_TYPES.len() + 1],
+    used_by: [u64; CONSUMERS.len()],
+}
+
+impl Stats {
+    /// The memory the frame allocator manages.
+    pub fn total(&self) -> u64 {
+        self.managed.iter().sum()
+    }
+
+    pub fn free(&self) -> u64 {
+        self.free.iter().sum()
+    }
+
+    pub fn used(&self) -> u64 {
+        self.total() - self.free()
+    }
+
+    /// The memory of `ty` the firmware reported, including the memory the allocator does not
+    /// manage.
+    pub fn reported(&self, ty: MemoryType) -> u64 {
+        self.reported[type_index(ty)]
+    }
+
+    /// The memory of `ty` the allocator manages, and how much of it is free.
+    pub fn managed(&self, ty: MemoryType) -> (u64, u64) {
+        (self.managed[type_index(ty)], self.free[type_index(ty)])
+    }
+
+    pub fn used_by(&self, consumer: Consumer) -> u64 {
+        self.used_by[consumer.index()]
+    }
+
+    /// The sum of `used_by` over every consumer, which equals `used` unless the accounting is
+    /// broken.
+    pub fn used_by_consumers(&self) -> u64 {
+        self.used_by.iter().sum()
+    }
+
+    fn new() -> Self {
+        Self {
+            reported: [0; KNOWN_TYPES.len() + 1],
+            managed: [0; KNOWN_TYPES.len() + 1],
+            free: [0; KNOWN_TYPES.len() + 1],
+            used_by: [0; CONSUMERS.len()],
+        }
+    }
+}
+
+// A table with a line for each type and consumer.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Memory: {} KiB, free {} KiB, used {} KiB",
+            self.total() / 1024,
+            self.free() / 1024,
+            self.used() / 1024
+        )?;
+
+        for &ty in &KNOWN_TYPES {
+            let reported = self.reported(ty);
+            let (managed, free) = self.managed(ty);
+            if reported > 0 || managed > 0 {
+                writeln!(
+                    f,
+                    "  {:?}: reported {} KiB, managed {} KiB, free {} KiB",
+                    ty,
+                    reported / 1024,
+                    managed / 1024,
+                    free / 1024
+                )?;
+            }
+        }
+
+        let others = self.reported[KNOWN_TYPES.len()];
+        if others > 0 {
+            writeln!(f, "  Vendor-specific: reported {} KiB", others / 1024)?;
+        }
 
-                Some(PhysFrame::containing_address(addr))
+        for (i, &consumer) in CONSUMERS.iter().enumerate() {
+            write!(f, "  {:?}: {} KiB", consumer, self.used_by(consumer) / 1024)?;
+            if i + 1 < CONSUMERS.len() {
+                writeln!(f)?;
             }
         }
+
+        Ok(())
     }
 }
 
-impl FrameDeallocator<Size4KiB> for FrameManager {
-    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
-        let addr = frame.start_address();
-        Self::change_free_page_ptr(addr);
-        ptr::write(FREE_PAGE_ADDR.as_mut_ptr(), self.head);
-        self.head = Some(addr);
+#[derive(Copy, Clone)]
+struct Range {
+    start: u64,
+    end: u64,
+    ty: MemoryType,
+}
+
+/// Placed at the start of a free block.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Header {
+    magic: u64,
+    order: usize,
+    prev: Option<PhysAddr>,
+    next: Option<PhysAddr>,
+}
+
+// The identity mapping is removed after `mark_pages_as_unused`, so headers are accessed through
+// `FREE_PAGE_ADDR`.
+fn read_header(addr: PhysAddr) -> Header {
+    map_free_page(addr);
+    unsafe { ptr::read(FREE_PAGE_ADDR.as_ptr()) }
+}
+
+fn write_header(addr: PhysAddr, header: Header) {
+    map_free_page(addr);
+    unsafe { ptr::write(FREE_PAGE_ADDR.as_mut_ptr(), header) }
+}
+
+fn update_header(addr: PhysAddr, f: impl FnOnce(&mut Header)) {
+    let mut header = read_header(addr);
+    f(&mut header);
+    write_header(addr, header);
+}
+
+// Each CPU flushes its own TLB before using the page, so a stale entry on another CPU is never
+// used.
+fn map_free_page(addr: PhysAddr) {
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    unsafe {
+        ptr::write(
+            CHANGE_FREE_PAGE_ADDR.as_mut_ptr(),
+            addr.as_u64() | flags.bits(),
+        )
+    }
+    tlb::flush(FREE_PAGE_ADDR);
+}
+
+fn type_index(ty: MemoryType) -> usize {
+    KNOWN_TYPES
+        .iter()
+        .position(|&known| known == ty)
+        .unwrap_or(KNOWN_TYPES.len())
+}
+
+fn zone_of(addr: PhysAddr) -> usize {
+    if addr.as_u64() < LIMIT_ZONE_1MIB {
+        0
+    } else if addr.as_u64() < LIMIT_ZONE_4GIB {
+        1
+    } else {
+        2
     }
 }
+
+// Calls `f` with each part of `start..end` which no range in `excluded` covers. `excluded` must be
+// sorted by the start.
+fn for_each_uncovered(
+    mut start: u64,
+    end: u64,
+    excluded: &[(u64, u64)],
+    mut f: impl FnMut(u64, u64),
+) {
+    for &(excluded_start, excluded_end) in excluded {
+        if excluded_end <= start || excluded_start >= end {
+            continue;
+        }
+
+        if start < excluded_start {
+            f(start, excluded_start);
+        }
+
+        start = start.max(excluded_end);
+    }
+
+    if start < end {
+        f(start, end);
+    }
+}
+
+fn align_down(addr: u64) -> u64 {
+    addr / Size4KiB::SIZE * Size4KiB::SIZE
+}
+
+fn align_up(addr: u64) -> u64 {
+    align_down(addr + Size4KiB::SIZE - 1)
+}
+
+// The order of the largest block which starts at `addr`, ends by `end` and stays in a zone.
+fn largest_order(addr: PhysAddr, end: PhysAddr) -> usize {
+    let mut order = 0;
+    while order + 1 < NUM_OF_ORDERS {
+        let bytes = Size4KiB::SIZE << (order + 1);
+        if addr.as_u64() % bytes != 0
+            || addr.as_u64() + bytes > end.as_u64()
+            || zone_of(addr) != zone_of(addr + (bytes - 1))
+        {
+            break;
+        }
+
+        order += 1;
+    }
+
+    order
+}
@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// WORKAROUND: https://stackoverflow.com/questions/63933070/clippy-says-too-many-arguments-to-static-declaration
+#![allow(clippy::too_many_arguments)]
+
+use {
+    super::heap,
+    crate::{smp::percpu, sync::IrqSpinlock},
+    core::{
+        alloc::{GlobalAlloc, Layout},
+        cell::UnsafeCell,
+        fmt, ptr,
+        sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    x86_64::instructions::interrupts,
+};
+
+// Larger objects are allocated from the heap directly.
+const SIZE_CLASSES: [usize; NUM_OF_CLASSES] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+const NUM_OF_CLASSES: usize = 8;
+
+const BYTES_SLAB: usize = 0x4000;
+const ALIGN_SLAB: usize = 0x1000;
+
+const MAGAZINE_CAPACITY: usize = 32;
+
+#[global_allocator]
+static ALLOCATOR: Allocator = Allocator;
+
+static DEPOT: IrqSpinlock<Depot> = IrqSpinlock::new(Depot {
+    free: [FreeList(ptr::null_mut()); NUM_OF_CLASSES],
+    slabs: [0; NUM_OF_CLASSES],
+});
+
+static IN_USE: [AtomicUsize; NUM_OF_CLASSES] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+static ALLOCATIONS: [AtomicU64; NUM_OF_CLASSES] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+// Objects of each size class are carved from slabs taken from the heap. A freed object goes to the
+// magazine of the CPU, and a magazine exchanges objects with the depot only when it is empty or
+// full, so most allocations take no lock. Slabs are never returned to the heap.
+struct Allocator;
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match class_of(layout) {
+            Some(class) => class,
+            None => return heap::alloc(layout),
+        };
+
+        let object = interrupts::without_interrupts(|| match percpu::try_current() {
+            Some(cpu) => cpu.magazines().pop(class),
+            None => DEPOT.lock().pop(class),
+        });
+
+        if !object.is_null() {
+            IN_USE[class].fetch_add(1, Ordering::Relaxed);
+            ALLOCATIONS[class].fetch_add(1, Ordering::Relaxed);
+        }
+
+        object
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = match class_of(layout) {
+            Some(class) => class,
+            None => return heap::dealloc(ptr, layout),
+        };
+
+        interrupts::without_interrupts(|| match percpu::try_current() {
+            Some(cpu) => cpu.magazines().push(class, ptr),
+            None => DEPOT.lock().push(class, ptr),
+        });
+
+        IN_USE[class].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The objects each CPU keeps for itself.
+pub struct Magazines(UnsafeCell<[Magazine; NUM_OF_CLASSES]>);
+
+impl Default for Magazines {
+    fn default() -> Self {
+        Self(UnsafeCell::new(
+            [Magazine {
+                rounds: [ptr::null_mut(); MAGAZINE_CAPACITY],
+                len: 0,
+            }; NUM_OF_CLASSES],
+        ))
+    }
+}
+
+impl Magazines {
+    // Must be called with interrupts disabled, so that nothing else on this CPU touches the
+    // magazines. Returns null if the heap is exhausted.
+    fn pop(&self, class: usize) -> *mut u8 {
+        let magazine = unsafe { &mut (*self.0.get())[class] };
+
+        if magazine.len == 0 {
+            // Fill a half, so that the next few frees do not overflow it.
+            let mut depot = DEPOT.lock();
+            while magazine.len < MAGAZINE_CAPACITY / 2 {
+                let object = depot.pop(class);
+                if object.is_null() {
+                    break;
+                }
+
+                magazine.rounds[magazine.len] = object;
+                magazine.len += 1;
+            }
+        }
+
+        if magazine.len == 0 {
+            return ptr::null_mut();
+        }
+
+        magazine.len -= 1;
+        magazine.rounds[magazine.len]
+    }
+
+    // Must be called with interrupts disabled, like `pop`.
+    fn push(&self, class: usize, object: *mut u8) {
+        let magazine = unsafe { &mut (*self.0.get())[class] };
+
+        if magazine.len == MAGAZINE_CAPACITY {
+            let mut depot = DEPOT.lock();
+            while magazine.len > MAGAZINE_CAPACITY / 2 {
+                magazine.len -= 1;
+                depot.push(class, magazine.rounds[magazine.len]);
+            }
+        }
+
+        magazine.rounds[magazine.len] = object;
+        magazine.len += 1;
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Magazine {
+    rounds: [*mut u8; MAGAZINE_CAPACITY],
+    len: usize,
+}
+
+struct Depot {
+    free: [FreeList; NUM_OF_CLASSES],
+    slabs: [usize; NUM_OF_CLASSES],
+}
+
+// Every object is aligned to at least 16 bytes, so it can hold a pointer.
+#[allow(clippy::cast_ptr_alignment)]
+impl Depot {
+    fn pop(&mut self, class: usize) -> *mut u8 {
+        if self.free[class].0.is_null() && !self.add_slab(class) {
+            return ptr::null_mut();
+        }
+
+        let object = self.free[class].0;
+        self.free[class].0 = unsafe { *object.cast::<*mut u8>() };
+        object
+    }
+
+    fn push(&mut self, class: usize, object: *mut u8) {
+        unsafe { *object.cast::<*mut u8>() = self.free[class].0 };
+        self.free[class].0 = object;
+    }
+
+    fn add_slab(&mut self, class: usize) -> bool {
+        let slab = heap::alloc(Layout::from_size_align(BYTES_SLAB, ALIGN_SLAB).unwrap());
+        if slab.is_null() {
+            return false;
+        }
+
+        for i in (0..BYTES_SLAB / SIZE_CLASSES[class]).rev() {
+            self.push(class, unsafe { slab.add(i * SIZE_CLASSES[class]) });
+        }
+
+        self.slabs[class] += 1;
+        true
+    }
+}
+
+// Each free object holds the pointer to the next one.
+#[derive(Copy, Clone)]
+struct FreeList(*mut u8);
+
+// The objects are owned by the depot, which is behind a lock.
+unsafe impl Send for FreeList {}
+
+/// The usage of each size class.
+#[derive(Copy, Clone)]
+pub struct Stats {
+    in_use: [usize; NUM_OF_CLASSES],
+    allocations: [u64; NUM_OF_CLASSES],
+    slabs: [usize; NUM_OF_CLASSES],
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slab:")?;
+        for (i, size) in SIZE_CLASSES.iter().enumerate() {
+            write!(
+                f,
+                "\n  {} bytes: {} in use, {} allocations, {} slabs",
+                size, self.in_use[i], self.allocations[i], self.slabs[i]
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A snapshot of the statistics. The objects cached in the magazines count as free.
+pub fn stats() -> Stats {
+    let mut stats = Stats {
+        in_use: [0; NUM_OF_CLASSES],
+        allocations: [0; NUM_OF_CLASSES],
+        slabs: DEPOT.lock().slabs,
+    };
+
+    for (i, (in_use, allocations)) in IN_USE.iter().zip(&ALLOCATIONS).enumerate() {
+        stats.in_use[i] = in_use.load(Ordering::Relaxed);
+        stats.allocations[i] = allocations.load(Ordering::Relaxed);
+    }
+
+    stats
+}
+
+// An object of a size class is aligned to its size, as slabs are aligned to pages.
+fn class_of(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    SIZE_CLASSES.iter().position(|&class| size <= class)
+}
@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{
+        phys::{Consumer, Zone, FRAME_MANAGER},
+        virt,
+    },
+    core::convert::TryFrom,
+    os_units::{Bytes, Size},
+    x86_64::{
+        structures::paging::{PageSize, PhysFrame, Size4KiB},
+        VirtAddr,
+    },
+};
+
+/// A kernel stack of `2^order` pages with an unmapped guard page below it. The stack is unmapped
+/// and its frames are freed when this is dropped.
+pub struct Stack {
+    start: VirtAddr,
+    frame: PhysFrame,
+    order: usize,
+}
+
+impl Stack {
+    /// Returns `None` if no contiguous frames are available.
+    pub fn new(order: usize) -> Option<Self> {
+        let frame = FRAME_MANAGER
+            .lock()
+            .allocate(order, Zone::Any, Consumer::Stacks)?;
+        let start = virt::map_with_guard_page(frame.start_address(), bytes(order));
+
+        Some(Self {
+            start,
+            frame,
+            order,
+        })
+    }
+
+    /// The initial stack pointer, which is page-aligned.
+    pub fn end(&self) -> VirtAddr {
+        self.start + bytes(self.order).as_usize()
+    }
+
+    /// Whether `addr` is in the guard page below this stack.
+    pub fn is_in_guard_page(&self, addr: VirtAddr) -> bool {
+        (self.start - Size4KiB::SIZE..self.start).contains(&addr)
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        virt::unmap_with_guard_page(self.start, bytes(self.order));
+        unsafe {
+            FRAME_MANAGER
+                .lock()
+                .deallocate(self.frame, self.order, Consumer::Stacks);
+        }
+    }
+}
+
+fn bytes(order: usize) -> Size<Bytes> {
+    Size::new(usize::try_from(Size4KiB::SIZE << order).unwrap())
+}
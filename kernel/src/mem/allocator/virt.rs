@@ -1,15 +1,26 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    crate::mem::paging::pml4::PML4,
-    common::constant::LIMIT_VIRT_ADDR,
+    super::phys::FRAME_MANAGER,
+    crate::{mem::paging::pml4::PML4, smp, sync::IrqSpinlock},
+    common::constant::{BYTES_KERNEL_MAP, KERNEL_MAP_ADDR, LIMIT_VIRT_ADDR},
+    conquer_once::spin::Lazy,
     core::convert::TryFrom,
+    os_units::{Bytes, NumOfPages, Size},
     x86_64::{
-        structures::paging::{MapperAllSizes, Page, PageSize, Size4KiB},
-        VirtAddr,
+        structures::paging::{
+            mapper::MapToError, Mapper, MapperAllSizes, Page, PageSize, PageTableFlags, PhysFrame,
+            Size4KiB,
+        },
+        PhysAddr, VirtAddr,
     },
 };
 
+// Each `unmap` may split the free space, so this is far more than needed.
+const MAX_NUM_OF_FREE_RANGES: usize = 64;
+
+static WINDOW: Lazy<IrqSpinlock<Window>> = Lazy::new(|| IrqSpinlock::new(Window::new()));
+
 // TODO: Deallocate after calling passed closure.
 pub fn map_temporary<T>(f: T)
 where
@@ -21,6 +32,162 @@ where
     }
 }
 
+/// Maps the physical range to unused virtual pages and returns the virtual address which
+/// corresponds to `start`.
+pub fn map(start: PhysAddr, bytes: Size<Bytes>) -> VirtAddr {
+    map_with_flags(
+        start,
+        bytes,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
+    )
+}
+
+/// Same as `map`, but the pages are not cached, which is necessary for memory-mapped registers.
+pub fn map_mmio(start: PhysAddr, bytes: Size<Bytes>) -> VirtAddr {
+    map_with_flags(
+        start,
+        bytes,
+        PageTableFlags::PRESENT
+            | PageTableFlags::WRITABLE
+            | PageTableFlags::NO_EXECUTE
+            | PageTableFlags::NO_CACHE
+            | PageTableFlags::WRITE_THROUGH,
+    )
+}
+
+pub fn unmap(start: VirtAddr, bytes: Size<Bytes>) {
+    let page_start = start.align_down(Size4KiB::SIZE);
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes).as_usize();
+
+    unmap_pages(page_start, num_of_pages);
+    WINDOW
+        .lock()
+        .deallocate(page_start, num_of_pages as u64 * Size4KiB::SIZE);
+}
+
+/// Same as `map`, but the page below the mapped pages is left unmapped, so that a stack overflow
+/// causes a page fault instead of corrupting other memory.
+pub fn map_with_guard_page(start: PhysAddr, bytes: Size<Bytes>) -> VirtAddr {
+    let frame_start = start.align_down(Size4KiB::SIZE);
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes).as_usize();
+
+    let virt_start = allocate_window(num_of_pages + 1) + Size4KiB::SIZE;
+    map_pages(
+        virt_start,
+        frame_start,
+        num_of_pages,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
+    );
+
+    virt_start + (start - frame_start)
+}
+
+/// Unmaps the range mapped by `map_with_guard_page`.
+pub fn unmap_with_guard_page(start: VirtAddr, bytes: Size<Bytes>) {
+    let page_start = start.align_down(Size4KiB::SIZE);
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes).as_usize();
+
+    unmap_pages(page_start, num_of_pages);
+    WINDOW.lock().deallocate(
+        page_start - Size4KiB::SIZE,
+        (num_of_pages as u64 + 1) * Size4KiB::SIZE,
+    );
+}
+
+/// Maps the physical range to the same virtual addresses. Pages which are already identity-mapped
+/// are left untouched.
+///
+/// # Panics
+///
+/// This function panics if a page in the range is mapped to a different frame.
+pub fn identity_map(start: PhysAddr, bytes: Size<Bytes>, flags: PageTableFlags) {
+    let frame_start = start.align_down(Size4KiB::SIZE);
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes);
+
+    for i in 0..num_of_pages.as_usize() {
+        let addr = frame_start + Size4KiB::SIZE * i as u64;
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(addr.as_u64()));
+        let frame = PhysFrame::containing_address(addr);
+
+        let result = unsafe {
+            PML4.lock()
+                .map_to(page, frame, flags, &mut *FRAME_MANAGER.lock())
+        };
+
+        match result {
+            Ok(flush) => flush.flush(),
+            Err(MapToError::PageAlreadyMapped(mapped)) => assert_eq!(
+                mapped, frame,
+                "{:?} is already mapped to another frame.",
+                page
+            ),
+            Err(e) => panic!("Failed to identity-map {:?}: {:?}", addr, e),
+        }
+    }
+}
+
+/// Unmaps the range mapped by `identity_map`.
+pub fn identity_unmap(start: PhysAddr, bytes: Size<Bytes>) {
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes).as_usize();
+    unmap_pages(
+        VirtAddr::new(start.align_down(Size4KiB::SIZE).as_u64()),
+        num_of_pages,
+    );
+}
+
+fn map_with_flags(start: PhysAddr, bytes: Size<Bytes>, flags: PageTableFlags) -> VirtAddr {
+    let frame_start = start.align_down(Size4KiB::SIZE);
+    let num_of_pages = num_of_pages_covering(start.as_u64(), bytes).as_usize();
+
+    let virt_start = allocate_window(num_of_pages);
+    map_pages(virt_start, frame_start, num_of_pages, flags);
+
+    virt_start + (start - frame_start)
+}
+
+fn allocate_window(num_of_pages: usize) -> VirtAddr {
+    match WINDOW.lock().allocate(num_of_pages as u64 * Size4KiB::SIZE) {
+        Some(addr) => addr,
+        None => panic!("OOM during `virt::map`"),
+    }
+}
+
+fn map_pages(
+    virt_start: VirtAddr,
+    frame_start: PhysAddr,
+    num_of_pages: usize,
+    flags: PageTableFlags,
+) {
+    for i in 0..num_of_pages {
+        let offset = Size4KiB::SIZE * i as u64;
+        let page = Page::<Size4KiB>::containing_address(virt_start + offset);
+        let frame = PhysFrame::containing_address(frame_start + offset);
+
+        unsafe {
+            PML4.lock()
+                .map_to(page, frame, flags, &mut *FRAME_MANAGER.lock())
+                .expect("Failed to map a page.")
+                .flush();
+        }
+    }
+}
+
+fn unmap_pages(virt_start: VirtAddr, num_of_pages: usize) {
+    for i in 0..num_of_pages {
+        let page = Page::<Size4KiB>::containing_address(virt_start + Size4KiB::SIZE * i as u64);
+        let (_, flush) = PML4.lock().unmap(page).expect("Failed to unmap a page.");
+        flush.flush();
+    }
+
+    // The addresses are reused, so no CPU may keep the old translations.
+    smp::flush_tlb_of_others();
+}
+
+fn num_of_pages_covering(start: u64, bytes: Size<Bytes>) -> Size<NumOfPages<Size4KiB>> {
+    let offset = start % Size4KiB::SIZE;
+    Size::<Bytes>::new(usize::try_from(offset).unwrap() + bytes.as_usize()).as_num_of_pages()
+}
+
 pub fn search_first_unused_page() -> Option<Page> {
     for addr in (0..LIMIT_VIRT_ADDR.as_u64()).step_by(usize::try_from(Size4KiB::SIZE).unwrap()) {
         let virt_addr = VirtAddr::new(addr);
@@ -30,3 +197,85 @@ pub fn search_first_unused_page() -> Option<Page> {
     }
     None
 }
+
+// The free parts of the window at `KERNEL_MAP_ADDR`, sorted by address. The window is in the
+// higher half, so a kernel mapping never takes the place of an identity mapping.
+struct Window {
+    free: [Range; MAX_NUM_OF_FREE_RANGES],
+    num_of_ranges: usize,
+}
+
+impl Window {
+    fn new() -> Self {
+        let mut free = [Range { start: 0, end: 0 }; MAX_NUM_OF_FREE_RANGES];
+        free[0] = Range {
+            start: KERNEL_MAP_ADDR.as_u64(),
+            end: KERNEL_MAP_ADDR.as_u64() + BYTES_KERNEL_MAP.as_usize() as u64,
+        };
+
+        Self {
+            free,
+            num_of_ranges: 1,
+        }
+    }
+
+    // First fit.
+    fn allocate(&mut self, bytes: u64) -> Option<VirtAddr> {
+        let i = self.free[..self.num_of_ranges]
+            .iter()
+            .position(|range| range.end - range.start >= bytes)?;
+
+        let start = self.free[i].start;
+        self.free[i].start += bytes;
+        if self.free[i].start == self.free[i].end {
+            self.remove(i);
+        }
+
+        Some(VirtAddr::new(start))
+    }
+
+    fn deallocate(&mut self, start: VirtAddr, bytes: u64) {
+        let start = start.as_u64();
+        let end = start + bytes;
+
+        let n = self.num_of_ranges;
+        let i = self.free[..n]
+            .iter()
+            .position(|range| range.start >= end)
+            .unwrap_or(n);
+
+        let joins_prev = i > 0 && self.free[i - 1].end == start;
+        let joins_next = i < n && self.free[i].start == end;
+
+        match (joins_prev, joins_next) {
+            (true, true) => {
+                self.free[i - 1].end = self.free[i].end;
+                self.remove(i);
+            }
+            (true, false) => self.free[i - 1].end = end,
+            (false, true) => self.free[i].start = start,
+            (false, false) if n == MAX_NUM_OF_FREE_RANGES => {
+                warn!(
+                    "Too many free virtual ranges. {:#x}-{:#x} is leaked.",
+                    start, end
+                );
+            }
+            (false, false) => {
+                self.free.copy_within(i..n, i + 1);
+                self.free[i] = Range { start, end };
+                self.num_of_ranges += 1;
+            }
+        }
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.free.copy_within(i + 1..self.num_of_ranges, i);
+        self.num_of_ranges -= 1;
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Range {
+    start: u64,
+    end: u64,
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    alloc::vec::Vec,
+    uefi::table::boot::MemoryDescriptor,
+    x86_64::structures::paging::{PageSize, Size4KiB},
+};
+
+/// Logs the memory map the firmware reported. Adjacent ranges of the same type and attributes are
+/// shown as one.
+pub fn print(mem_map: &[MemoryDescriptor]) {
+    let mut descriptors: Vec<_> = mem_map.to_vec();
+    descriptors.sort_unstable_by_key(|descriptor| descriptor.phys_start);
+
+    let mut merged: Vec<MemoryDescriptor> = Vec::new();
+    for descriptor in descriptors {
+        match merged.last_mut() {
+            Some(last)
+                if last.ty == descriptor.ty
+                    && last.att == descriptor.att
+                    && end(last) == descriptor.phys_start =>
+            {
+                last.page_count += descriptor.page_count
+            }
+            _ => merged.push(descriptor),
+        }
+    }
+
+    info!("Memory map:");
+    for descriptor in merged {
+        info!(
+            "  {:#014x}-{:#014x} {:>10} KiB {:?}",
+            descriptor.phys_start,
+            end(&descriptor),
+            descriptor.page_count * Size4KiB::SIZE / 1024,
+            descriptor.ty
+        );
+    }
+}
+
+fn end(descriptor: &MemoryDescriptor) -> u64 {
+    descriptor.phys_start + descriptor.page_count * Size4KiB::SIZE
+}
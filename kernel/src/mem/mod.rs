@@ -1,4 +1,5 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 pub mod allocator;
+pub mod map;
 pub mod paging;
@@ -2,7 +2,14 @@
 
 pub mod pml4;
 
-use {common::constant::RECUR_PML4_ADDR, x86_64::structures::paging::PageTable};
+use {
+    common::constant::{RECUR_PML4_ADDR, STACK_GUARD_PAGE},
+    pml4::PML4,
+    x86_64::{
+        instructions::tlb,
+        structures::paging::{Mapper, Page, PageTable, Size4KiB},
+    },
+};
 
 pub fn mark_pages_as_unused() {
     let page_table = unsafe { &mut *(RECUR_PML4_ADDR.as_mut_ptr() as *mut PageTable) };
@@ -11,4 +18,17 @@ pub fn mark_pages_as_unused() {
     for i in 0..510 {
         page_table[i].set_unused();
     }
+
+    // The tables of the removed entries may be reclaimed, so the CPU must not use their cached
+    // copies.
+    tlb::flush_all();
+}
+
+/// Makes sure that the guard page below the stack is not mapped. Nothing maps it currently, but
+/// an overflow would silently corrupt the memory if something did.
+pub fn unmap_stack_guard_page() {
+    let page = Page::<Size4KiB>::containing_address(STACK_GUARD_PAGE);
+    if let Ok((_, flush)) = PML4.lock().unmap(page) {
+        flush.flush();
+    }
 }
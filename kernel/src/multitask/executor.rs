@@ -1,96 +1,267 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    super::task::{self, Task},
-    alloc::{collections::BTreeMap, sync::Arc, task::Wake},
-    core::task::{Context, Poll, Waker},
+    super::{
+        task::{self, JoinHandle, Runnable, Task},
+        thread,
+    },
+    crate::{interrupt, smp::percpu, sync::IrqSpinlock, time::Duration},
+    alloc::{collections::BTreeMap, sync::Arc, task::Wake, vec::Vec},
+    core::{
+        future::Future,
+        mem,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        task::{Context, Waker},
+    },
     crossbeam_queue::ArrayQueue,
-    x86_64::instructions::interrupts::{self, enable_interrupts_and_hlt},
+    x86_64::instructions::interrupts,
 };
 
+// A poll taking longer than this blocks the other tasks of the executor noticeably. It is compared
+// with the run time of the thread, as the thread may be preempted in the middle of a poll.
+const SLOW_POLL: Duration = Duration::from_millis(100);
+
+const WAKE_QUEUE_CAPACITY: usize = 100;
+
+// Tasks spawned by `spawn` while no executor is running. Any executor may take them. An idle
+// executor checks this with interrupts disabled, so the lock must not be held by a preempted
+// thread.
+static UNASSIGNED: IrqSpinlock<Vec<SendRunnable>> = IrqSpinlock::new(Vec::new());
+
+// The inboxes of the running executors, which may be on any CPU.
+static INBOXES: IrqSpinlock<Vec<Arc<Inbox>>> = IrqSpinlock::new(Vec::new());
+static NEXT_INBOX: AtomicUsize = AtomicUsize::new(0);
+
+/// Spawns `task` on one of the running executors. The executors are chosen in turn, so tasks are
+/// spread over all CPUs. Unlike `Executor::spawn`, this can be called from inside a task.
+pub fn spawn<F>(task: Task<F>) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send,
+{
+    let (runnable, handle) = task.into_runnable();
+    let runnable = SendRunnable(runnable);
+
+    let inbox = {
+        let inboxes = INBOXES.lock();
+        if inboxes.is_empty() {
+            None
+        } else {
+            let i = NEXT_INBOX.fetch_add(1, Ordering::Relaxed) % inboxes.len();
+            Some(inboxes[i].clone())
+        }
+    };
+
+    match inbox {
+        Some(inbox) => inbox.push(runnable),
+        None => UNASSIGNED.lock().push(runnable),
+    }
+
+    handle
+}
+
+// `Runnable` is not `Send` as it erases the type of the future.
+struct SendRunnable(Runnable);
+
+// SAFETY: `spawn` only accepts `Send` futures and outputs.
+unsafe impl Send for SendRunnable {}
+
+/// Runs tasks on the CPU where it is created. Each executor has its own queues, and the tasks
+/// spawned by `spawn` are distributed among them.
 pub struct Executor {
-    tasks: BTreeMap<task::Id, Task>,
-    woken_id_queue: Arc<ArrayQueue<task::Id>>,
-    waker_cache: BTreeMap<task::Id, Waker>,
+    tasks: BTreeMap<task::Id, Runnable>,
+    wakers: BTreeMap<task::Id, Arc<TaskWaker>>,
+    wake_queue: Arc<WakeQueue>,
+    inbox: Arc<Inbox>,
 }
 
 impl Executor {
     pub fn new() -> Self {
+        let apic_id = percpu::current().apic_id();
+
         Self {
             tasks: BTreeMap::new(),
-            woken_id_queue: Arc::new(ArrayQueue::new(100)),
-            waker_cache: BTreeMap::new(),
+            wakers: BTreeMap::new(),
+            wake_queue: Arc::new(WakeQueue::new(apic_id)),
+            inbox: Arc::new(Inbox::new(apic_id)),
         }
     }
 
-    pub fn spawn(&mut self, task: Task) {
-        let id = task.id();
-        if self.tasks.insert(id, task).is_some() {
-            panic!("Task ID confliction!");
-        }
-
-        self.woken_id_queue
-            .push(id)
-            .expect("woken_id_queue is full");
+    pub fn spawn<F: Future + 'static>(&mut self, task: Task<F>) -> JoinHandle<F::Output> {
+        let (runnable, handle) = task.into_runnable();
+        self.add(runnable);
+        handle
     }
 
     pub fn run(&mut self) -> ! {
+        INBOXES.lock().push(self.inbox.clone());
+
         loop {
+            self.take_spawned_tasks();
             self.run_woken_tasks();
             self.sleep_if_idle();
         }
     }
 
+    fn add(&mut self, runnable: Runnable) {
+        let id = runnable.id();
+        if let Some(task) = self.tasks.insert(id, runnable) {
+            panic!("{} conflicts with another task.", task);
+        }
+
+        let waker = Arc::new(TaskWaker::new(id, self.wake_queue.clone()));
+        waker.wake_task();
+        self.wakers.insert(id, waker);
+    }
+
+    fn take_spawned_tasks(&mut self) {
+        let received = mem::take(&mut *self.inbox.tasks.lock());
+        let unassigned = mem::take(&mut *UNASSIGNED.lock());
+        for SendRunnable(runnable) in received.into_iter().chain(unassigned) {
+            self.add(runnable);
+        }
+    }
+
     fn sleep_if_idle(&self) {
         interrupts::disable();
-        if self.woken_id_queue.is_empty() {
-            enable_interrupts_and_hlt()
+        if self.wake_queue.is_empty()
+            && self.inbox.tasks.lock().is_empty()
+            && UNASSIGNED.lock().is_empty()
+        {
+            thread::idle()
         } else {
             interrupts::enable()
         }
     }
 
     fn run_woken_tasks(&mut self) {
-        let Self {
-            tasks,
-            woken_id_queue,
-            waker_cache,
-        } = self;
-
-        while let Some(id) = woken_id_queue.pop() {
-            let task = match tasks.get_mut(&id) {
-                Some(task) => task,
-                None => continue,
-            };
-
-            let waker = waker_cache
-                .entry(id)
-                .or_insert_with(|| TaskWaker::create_waker(id, woken_id_queue.clone()));
-
-            let mut context = Context::from_waker(waker);
-            match task.poll(&mut context) {
-                Poll::Ready(_) => {
-                    tasks.remove(&id);
-                    waker_cache.remove(&id);
-                }
-                Poll::Pending => {}
+        loop {
+            while let Some(id) = self.wake_queue.queue.pop() {
+                self.run_task_if_woken(id);
+            }
+
+            if !self.wake_queue.overflowed.swap(false, Ordering::AcqRel) {
+                break;
             }
+
+            // Some ids were not queued. Find them by the flags.
+            let woken: Vec<task::Id> = self
+                .wakers
+                .iter()
+                .filter(|(_, waker)| waker.woken.load(Ordering::Acquire))
+                .map(|(id, _)| *id)
+                .collect();
+
+            for id in woken {
+                self.run_task_if_woken(id);
+            }
+        }
+    }
+
+    fn run_task_if_woken(&mut self, id: task::Id) {
+        let (task, waker) = match (self.tasks.get_mut(&id), self.wakers.get(&id)) {
+            (Some(task), Some(waker)) => (task, waker),
+            _ => return,
+        };
+
+        // The id may be queued and found by the flag at the same time.
+        if !waker.woken.swap(false, Ordering::AcqRel) {
+            return;
+        }
+
+        let waker = Waker::from(waker.clone());
+        let mut context = Context::from_waker(&waker);
+        let start = thread::run_time();
+        let poll = task.poll(&mut context);
+
+        let elapsed = thread::run_time() - start;
+        if elapsed >= SLOW_POLL {
+            warn!(
+                "{} blocked the executor on CPU {} for {:?}.",
+                task,
+                percpu::current().index(),
+                elapsed
+            );
+        }
+
+        if poll.is_ready() {
+            self.tasks.remove(&id);
+            self.wakers.remove(&id);
+        }
+    }
+}
+
+/// The tasks sent to an executor by `spawn`.
+struct Inbox {
+    // The CPU the executor runs on.
+    apic_id: u8,
+    tasks: IrqSpinlock<Vec<SendRunnable>>,
+}
+
+impl Inbox {
+    fn new(apic_id: u8) -> Self {
+        Self {
+            apic_id,
+            tasks: IrqSpinlock::new(Vec::new()),
+        }
+    }
+
+    fn push(&self, runnable: SendRunnable) {
+        self.tasks.lock().push(runnable);
+        interrupt::wake_up(self.apic_id);
+    }
+}
+
+/// The ids of the woken tasks. Waking a task may happen in an interrupt handler, so it must
+/// neither allocate nor fail. If the queue is full, the id is left to be found by the flag of the
+/// task instead.
+struct WakeQueue {
+    queue: ArrayQueue<task::Id>,
+    overflowed: AtomicBool,
+    // The CPU the executor runs on, which may be halted while the queue is empty.
+    apic_id: u8,
+}
+
+impl WakeQueue {
+    fn new(apic_id: u8) -> Self {
+        Self {
+            queue: ArrayQueue::new(WAKE_QUEUE_CAPACITY),
+            overflowed: AtomicBool::new(false),
+            apic_id,
         }
     }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty() && !self.overflowed.load(Ordering::Acquire)
+    }
 }
 
 struct TaskWaker {
     id: task::Id,
-    task_queue: Arc<ArrayQueue<task::Id>>,
+    // Set while the task waits to be polled, so that a task is queued at most once.
+    woken: AtomicBool,
+    wake_queue: Arc<WakeQueue>,
 }
 
 impl TaskWaker {
-    fn create_waker(id: task::Id, task_queue: Arc<ArrayQueue<task::Id>>) -> Waker {
-        Waker::from(Arc::new(Self { id, task_queue }))
+    fn new(id: task::Id, wake_queue: Arc<WakeQueue>) -> Self {
+        Self {
+            id,
+            woken: AtomicBool::new(false),
+            wake_queue,
+        }
     }
 
     fn wake_task(&self) {
-        self.task_queue.push(self.id).expect("task_queue is full")
+        if self.woken.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        if self.wake_queue.queue.push(self.id).is_err() {
+            self.wake_queue.overflowed.store(true, Ordering::Release);
+        }
+
+        interrupt::wake_up(self.wake_queue.apic_id);
     }
 }
 
@@ -2,3 +2,6 @@
 
 pub mod executor;
 pub mod task;
+pub mod thread;
+
+pub use executor::spawn;
@@ -1,17 +1,20 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use {
-    alloc::boxed::Box,
+    alloc::{boxed::Box, sync::Arc},
     core::{
+        fmt,
         future::Future,
         pin::Pin,
         sync::atomic::{AtomicU64, Ordering},
-        task::{Context, Poll},
+        task::{Context, Poll, Waker},
     },
+    pin_project_lite::pin_project,
+    spinning_top::Spinlock,
 };
 
 #[derive(PartialOrd, PartialEq, Ord, Eq, Copy, Clone, Debug)]
-pub(super) struct Id(u64);
+pub struct Id(u64);
 
 impl Id {
     fn new() -> Self {
@@ -20,20 +23,57 @@ impl Id {
     }
 }
 
-pub struct Task {
-    id: Id,
-    future: Pin<Box<dyn Future<Output = ()>>>,
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A future to be spawned, with an optional name shown in diagnostics.
+pub struct Task<F: Future> {
+    name: Option<&'static str>,
+    future: F,
 }
 
-impl Task {
-    pub fn new(future: impl Future<Output = ()> + 'static) -> Self {
+impl<F: Future + 'static> Task<F> {
+    pub fn new(future: F) -> Self {
+        Self { name: None, future }
+    }
+
+    pub fn named(name: &'static str, future: F) -> Self {
         Self {
-            id: Id::new(),
-            future: Box::pin(future),
+            name: Some(name),
+            future,
         }
     }
 
-    pub fn poll(&mut self, context: &mut Context) -> Poll<()> {
+    /// Splits the task into the part which the executor runs and the handle to its output.
+    pub(super) fn into_runnable(self) -> (Runnable, JoinHandle<F::Output>) {
+        let id = Id::new();
+        let shared = Arc::new(Spinlock::new(Shared::new()));
+
+        let runnable = Runnable {
+            id,
+            name: self.name,
+            future: Box::pin(Joinable {
+                future: self.future,
+                shared: shared.clone(),
+            }),
+        };
+
+        (runnable, JoinHandle { id, shared })
+    }
+}
+
+/// A task in an executor. The output is sent to its `JoinHandle`.
+pub(super) struct Runnable {
+    id: Id,
+    name: Option<&'static str>,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Runnable {
+    pub(super) fn poll(&mut self, context: &mut Context) -> Poll<()> {
         self.future.as_mut().poll(context)
     }
 
@@ -41,3 +81,120 @@ impl Task {
         self.id
     }
 }
+
+impl fmt::Display for Runnable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "Task {} ({})", self.id, name),
+            None => write!(f, "Task {}", self.id),
+        }
+    }
+}
+
+/// Awaits the output of a task. Dropping the handle detaches the task rather than cancelling it.
+pub struct JoinHandle<T> {
+    id: Id,
+    shared: Arc<Spinlock<Shared<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Cancels the task. The task is dropped the next time the executor polls it, and the handle
+    /// completes with `Aborted`. This does nothing if the task has already completed.
+    pub fn abort(&self) {
+        let waker = {
+            let mut shared = self.shared.lock();
+            if shared.output.is_some() {
+                return;
+            }
+
+            shared.aborted = true;
+            shared.task_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, Aborted>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock();
+        match shared.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                shared.join_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Aborted;
+
+struct Shared<T> {
+    output: Option<Result<T, Aborted>>,
+    aborted: bool,
+    task_waker: Option<Waker>,
+    join_waker: Option<Waker>,
+}
+
+impl<T> Shared<T> {
+    fn new() -> Self {
+        Self {
+            output: None,
+            aborted: false,
+            task_waker: None,
+            join_waker: None,
+        }
+    }
+
+    fn complete(&mut self, output: Result<T, Aborted>) {
+        self.output = Some(output);
+        if let Some(waker) = self.join_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pin_project! {
+    /// Runs `future` unless the task is aborted, and stores the output for the `JoinHandle`.
+    struct Joinable<F: Future> {
+        #[pin]
+        future: F,
+        shared: Arc<Spinlock<Shared<F::Output>>>,
+    }
+}
+
+impl<F: Future> Future for Joinable<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.project();
+
+        {
+            let mut shared = this.shared.lock();
+            if shared.aborted {
+                shared.complete(Err(Aborted));
+                return Poll::Ready(());
+            }
+
+            shared.task_waker = Some(cx.waker().clone());
+        }
+
+        match this.future.poll(cx) {
+            Poll::Ready(output) => {
+                this.shared.lock().complete(Ok(output));
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
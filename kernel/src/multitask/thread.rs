@@ -0,0 +1,350 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        cpu::extended_state::ExtendedState,
+        mem::allocator::stack::Stack,
+        smp::percpu,
+        time::{Duration, Instant},
+    },
+    alloc::{boxed::Box, collections::VecDeque, vec::Vec},
+    core::{mem, ptr},
+    spinning_top::Spinlock,
+    x86_64::{
+        instructions::interrupts::{self, enable_interrupts_and_hlt},
+        VirtAddr,
+    },
+};
+
+// 64 KiB.
+const ORDER_STACK: usize = 4;
+
+type Entry = Box<dyn FnOnce() + Send>;
+
+/// A thread with a higher priority runs for a longer time slice. Every ready thread gets its turn,
+/// so a low priority thread is never starved and a thread spinning on a lock never blocks its
+/// holder forever.
+#[derive(Copy, Clone, Debug)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// In ticks.
+    fn time_slice(self) -> u64 {
+        match self {
+            Self::Low => 1,
+            Self::Normal => 2,
+            Self::High => 4,
+        }
+    }
+}
+
+/// Makes the running code the boot thread of this CPU, so that other threads can be spawned. Each
+/// CPU has its own scheduler, and a thread never moves to another CPU.
+pub fn init() {
+    percpu::current()
+        .scheduler()
+        .try_init_once(|| Spinlock::new(Scheduler::new()))
+        .expect("The scheduler is already initialized.");
+}
+
+/// Runs `f` in a new thread on this CPU. The thread exits when `f` returns.
+pub fn spawn<F>(priority: Priority, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    // A thread cannot free its own stack, so the stacks of exited threads are freed here.
+    let dead = interrupts::without_interrupts(|| mem::take(&mut scheduler().lock().dead));
+    drop(dead);
+
+    let thread = Box::new(Thread::new(priority, Box::new(f)));
+    interrupts::without_interrupts(|| scheduler().lock().ready.push_back(thread));
+}
+
+/// Gives the CPU to a thread which is not idle, or halts until the next interrupt if every thread
+/// is idle. This must be called with interrupts disabled, and it returns with them enabled.
+pub fn idle() {
+    let switched = try_scheduler().is_some()
+        && switch(
+            |ready| {
+                let i = ready.iter().position(|thread| !thread.idle)?;
+                ready.remove(i)
+            },
+            true,
+        );
+
+    if !switched {
+        enable_interrupts_and_hlt();
+        interrupts::disable();
+
+        // The interrupt may have woken any thread.
+        if let Some(scheduler) = try_scheduler() {
+            for thread in &mut scheduler.lock().ready {
+                thread.idle = false;
+            }
+        }
+    }
+
+    if let Some(scheduler) = try_scheduler() {
+        scheduler.lock().current.idle = false;
+    }
+
+    interrupts::enable();
+}
+
+/// Terminates the current thread.
+pub fn exit() -> ! {
+    interrupts::disable();
+
+    let mut scheduler = scheduler().lock();
+    let next = scheduler
+        .ready
+        .pop_front()
+        .expect("The last thread tried to exit.");
+    let mut previous = mem::replace(&mut scheduler.current, next);
+    scheduler.ticks = 0;
+    scheduler.switched_in = Instant::now();
+
+    // Moving the box does not move the thread, so the pointer stays valid.
+    let previous_rsp: *mut u64 = &mut previous.rsp;
+    let next_rsp = scheduler.current.rsp;
+    let next_state: *const ExtendedState = &scheduler.current.extended_state;
+    scheduler.dead.push(previous);
+    drop(scheduler);
+
+    unsafe {
+        (*next_state).restore();
+        switch_context(previous_rsp, next_rsp);
+    }
+    unreachable!("An exited thread is resumed.");
+}
+
+/// The time the current thread has run for. The time while it is preempted is not counted.
+pub fn run_time() -> Duration {
+    interrupts::without_interrupts(|| {
+        let scheduler = scheduler().lock();
+        scheduler.current.run_time + scheduler.switched_in.elapsed()
+    })
+}
+
+/// Whether `addr` is in the guard page of the stack of the current thread. Called from the
+/// exception handlers, so this returns `false` instead of waiting if the scheduler is locked.
+pub fn is_in_stack_guard_page(addr: VirtAddr) -> bool {
+    try_scheduler()
+        .and_then(Spinlock::try_lock)
+        .and_then(|scheduler| {
+            let stack = scheduler.current.stack.as_ref()?;
+            Some(stack.is_in_guard_page(addr))
+        })
+        .unwrap_or(false)
+}
+
+/// Counts the ticks of the current thread. Called from the timer interrupt.
+pub fn tick() {
+    if let Some(scheduler) = try_scheduler() {
+        let mut scheduler = scheduler.lock();
+        scheduler.ticks += 1;
+
+        if scheduler.ticks >= scheduler.current.priority.time_slice() && !scheduler.ready.is_empty()
+        {
+            scheduler.preemption_requested = true;
+        }
+    }
+}
+
+/// Switches to the next thread if the time slice of the current one is over. Called at the end of
+/// interrupt handlers, after EOI.
+pub fn preempt_if_requested() {
+    let requested = try_scheduler().map_or(false, |scheduler| {
+        mem::take(&mut scheduler.lock().preemption_requested)
+    });
+
+    if requested {
+        switch(|ready| ready.pop_front(), false);
+    }
+}
+
+/// Switches to the thread `pick` chooses, putting the current one at the end of the ready queue.
+/// Returns `false` without switching if `pick` chooses none. Interrupts must be disabled.
+///
+/// This never allocates, as the ready queue keeps its length. It can be called from interrupt
+/// handlers.
+fn switch(
+    pick: impl FnOnce(&mut VecDeque<Box<Thread>>) -> Option<Box<Thread>>,
+    idle: bool,
+) -> bool {
+    let (previous_rsp, next_rsp, previous_state, next_state) = {
+        let mut scheduler = scheduler().lock();
+        let next = match pick(&mut scheduler.ready) {
+            Some(next) => next,
+            None => return false,
+        };
+
+        let mut previous = mem::replace(&mut scheduler.current, next);
+        previous.idle = idle;
+        scheduler.ticks = 0;
+
+        let now = Instant::now();
+        previous.run_time += now - scheduler.switched_in;
+        scheduler.switched_in = now;
+
+        // Moving the box does not move the thread, so the pointers stay valid.
+        let previous_rsp: *mut u64 = &mut previous.rsp;
+        let previous_state: *mut ExtendedState = &mut previous.extended_state;
+        scheduler.ready.push_back(previous);
+
+        let next_state: *const ExtendedState = &scheduler.current.extended_state;
+        (
+            previous_rsp,
+            scheduler.current.rsp,
+            previous_state,
+            next_state,
+        )
+    };
+
+    // The caller saves the SSE registers it needs across the call, so the registers may be
+    // replaced before switching the stacks.
+    unsafe {
+        (*previous_state).save();
+        (*next_state).restore();
+        switch_context(previous_rsp, next_rsp);
+    }
+
+    true
+}
+
+fn scheduler() -> &'static Spinlock<Scheduler> {
+    try_scheduler().expect("The scheduler is not initialized.")
+}
+
+fn try_scheduler() -> Option<&'static Spinlock<Scheduler>> {
+    percpu::try_current()?.scheduler().try_get().ok()
+}
+
+/// The threads of a CPU. The lock is only taken with interrupts disabled, as the timer interrupt
+/// also takes it.
+pub struct Scheduler {
+    current: Box<Thread>,
+    ready: VecDeque<Box<Thread>>,
+    dead: Vec<Box<Thread>>,
+
+    // The ticks the current thread has run for.
+    ticks: u64,
+
+    // When the current thread started running this time.
+    switched_in: Instant,
+
+    // Switching threads before EOI would block the timer interrupt, so the timer only sets this
+    // flag and the switch happens after EOI.
+    preemption_requested: bool,
+}
+
+impl Scheduler {
+    fn new() -> Self {
+        Self {
+            current: Box::new(Thread::boot()),
+            ready: VecDeque::new(),
+            dead: Vec::new(),
+            ticks: 0,
+            switched_in: Instant::now(),
+            preemption_requested: false,
+        }
+    }
+}
+
+struct Thread {
+    priority: Priority,
+    // Valid only while the thread is not running.
+    rsp: u64,
+    // Set while the thread waits in `idle`.
+    idle: bool,
+    // Valid only while the thread is not running.
+    extended_state: ExtendedState,
+    // Excludes the time since the thread was switched in last.
+    run_time: Duration,
+    // `None` for the boot thread, which runs on the boot stack.
+    stack: Option<Stack>,
+}
+
+impl Thread {
+    fn boot() -> Self {
+        Self {
+            priority: Priority::Normal,
+            rsp: 0,
+            idle: false,
+            extended_state: ExtendedState::new(),
+            run_time: Duration::default(),
+            stack: None,
+        }
+    }
+
+    fn new(priority: Priority, entry: Entry) -> Self {
+        let stack = Stack::new(ORDER_STACK).expect("No memory for the stack of a thread.");
+
+        // The end of the stack is page-aligned, so `start` is entered with a 16-byte aligned stack.
+        let top = stack.end().as_mut_ptr::<u64>();
+
+        // The registers which `switch_context` pops, and the return address.
+        let entry = Box::into_raw(Box::new(entry));
+        let rsp = unsafe {
+            ptr::write_bytes(top.sub(7), 0, 7);
+            top.sub(1).write(start_trampoline as usize as u64);
+            top.sub(4).write(entry as u64); // r12
+
+            top.sub(7) as u64
+        };
+
+        Self {
+            priority,
+            rsp,
+            idle: false,
+            extended_state: ExtendedState::new(),
+            run_time: Duration::default(),
+            stack: Some(stack),
+        }
+    }
+}
+
+/// Saves the callee-saved registers on the current stack and stores the stack pointer to
+/// `previous_rsp`, then restores them from `next_rsp`. The other registers are saved by the
+/// caller, or by the interrupt handler if the thread is preempted.
+#[naked]
+unsafe extern "C" fn switch_context(previous_rsp: *mut u64, next_rsp: u64) {
+    asm!(
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov [rdi], rsp",
+        "mov rsp, rsi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "ret",
+        options(noreturn)
+    );
+}
+
+// A new thread starts here with the pointer to its entry in r12.
+#[naked]
+unsafe extern "C" fn start_trampoline() -> ! {
+    asm!("mov rdi, r12", "call {}", sym start, options(noreturn));
+}
+
+extern "C" fn start(entry: *mut Entry) -> ! {
+    let entry = unsafe { Box::from_raw(entry) };
+
+    // Threads are switched with interrupts disabled.
+    interrupts::enable();
+    entry();
+
+    exit();
+}
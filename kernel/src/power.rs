@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        acpi::{self, fadt::Fadt},
+        efi,
+    },
+    common::constant::{
+        KEY_CMD_PULSE_RESET, KEY_STATUS_SEND_NOT_READY, PORT_KEY_CMD, PORT_KEY_STATUS,
+    },
+    core::hint,
+    uefi::table::runtime::ResetType,
+    x86_64::instructions::{
+        interrupts,
+        port::Port,
+        tables::{lidt, DescriptorTablePointer},
+    },
+};
+
+// See ACPI Specification 6.3, 4.8.3.2.1.
+const SCI_EN: u16 = 1;
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_TYP_MASK: u16 = 0b111 << SLP_TYP_SHIFT;
+const SLP_EN: u16 = 1 << 13;
+
+// There is no timer yet. Each method is given this many iterations before the next one is tried.
+const SPIN_COUNT: usize = 10_000_000;
+
+pub fn shutdown() -> ! {
+    interrupts::disable();
+    info!("Shutting down...");
+
+    if let (Some(fadt), Some(s5)) = (acpi::fadt(), acpi::s5()) {
+        enter_s5(fadt, s5);
+    } else {
+        warn!("ACPI does not support powering off.");
+    }
+
+    efi::reset(ResetType::Shutdown);
+
+    error!("Failed to power off. It is now safe to turn off the computer.");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+pub fn reboot() -> ! {
+    interrupts::disable();
+    info!("Rebooting...");
+
+    if let Some((register, value)) = acpi::fadt().and_then(Fadt::reset) {
+        unsafe { register.write(u64::from(value)) };
+        spin();
+        warn!("Failed to reboot with the ACPI reset register.");
+    }
+
+    efi::reset(ResetType::Cold);
+
+    if acpi::fadt().map_or(true, Fadt::has_8042) {
+        pulse_reset_line();
+        spin();
+        warn!("Failed to reboot with the keyboard controller.");
+    }
+
+    triple_fault();
+}
+
+fn enter_s5(fadt: &Fadt, s5: acpi::dsdt::SleepType) {
+    let pm1a = match fadt.pm1a_cnt() {
+        Some(pm1a) => pm1a,
+        None => {
+            warn!("FADT has no PM1a control block.");
+            return;
+        }
+    };
+
+    if unsafe { pm1a.read() } & SCI_EN == 0 {
+        if let Some((port, value)) = fadt.acpi_enable_command() {
+            unsafe { Port::<u8>::new(port).write(value) };
+            for _ in 0..SPIN_COUNT {
+                if unsafe { pm1a.read() } & SCI_EN != 0 {
+                    break;
+                }
+                hint::spin_loop();
+            }
+        }
+    }
+
+    unsafe {
+        write_sleep_type(pm1a, s5.a());
+        if let Some(pm1b) = fadt.pm1b_cnt() {
+            write_sleep_type(pm1b, s5.b());
+        }
+    }
+
+    spin();
+    warn!("Failed to enter S5.");
+}
+
+unsafe fn write_sleep_type(register: acpi::fadt::PmRegister, sleep_type: u8) {
+    let value = register.read() & !SLP_TYP_MASK;
+    let sleep_type = (u16::from(sleep_type) << SLP_TYP_SHIFT) & SLP_TYP_MASK;
+    register.write(value | sleep_type | SLP_EN);
+}
+
+fn pulse_reset_line() {
+    let mut port_key_status = PORT_KEY_STATUS;
+    for _ in 0..SPIN_COUNT {
+        if unsafe { port_key_status.read() } & KEY_STATUS_SEND_NOT_READY == 0 {
+            break;
+        }
+        hint::spin_loop();
+    }
+
+    let mut port_key_cmd = PORT_KEY_CMD;
+    unsafe { port_key_cmd.write(KEY_CMD_PULSE_RESET) };
+}
+
+// An exception with an empty IDT causes a double fault, and then a triple fault, which resets the
+// CPU.
+fn triple_fault() -> ! {
+    let idtr = DescriptorTablePointer { limit: 0, base: 0 };
+    unsafe {
+        lidt(&idtr);
+        asm!("int3", options(noreturn));
+    }
+}
+
+fn spin() {
+    for _ in 0..SPIN_COUNT {
+        hint::spin_loop();
+    }
+}
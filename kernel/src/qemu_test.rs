@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        acpi,
+        mem::allocator::{heap, phys},
+        multitask::{
+            self,
+            executor::Executor,
+            task::Task,
+            thread::{self, Priority},
+        },
+        smp,
+        sync::{mpsc, oneshot, Mutex},
+        time::{self, Duration, Instant},
+    },
+    alloc::{alloc::Layout, sync::Arc, vec::Vec},
+    core::{
+        arch::x86_64::{_mm_getcsr, _mm_setcsr},
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicBool, AtomicU64, Ordering},
+        task::{Context, Poll},
+    },
+    futures_util::{future, task::AtomicWaker},
+    qemu_exit::QEMUExit,
+};
+
+// Far more than the capacity of the wake queue of an executor.
+const NUM_OF_STRESS_TASKS: usize = 2000;
+const NUM_OF_YIELDS: usize = 3;
+
+const NUM_OF_BENCHMARK_ALLOCATIONS: u32 = 10000;
+const BYTES_BENCHMARK_OBJECT: usize = 64;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Rounds toward zero, unlike the default.
+const SPINNER_MXCSR: u32 = 0x7f80;
+
+static SPINS: AtomicU64 = AtomicU64::new(0);
+static MXCSR_CHANGED: AtomicBool = AtomicBool::new(false);
+static JOINED: AtomicBool = AtomicBool::new(false);
+static STRESSED: AtomicBool = AtomicBool::new(false);
+static SYNCED: AtomicBool = AtomicBool::new(false);
+static BENCHMARKED: AtomicBool = AtomicBool::new(false);
+
+/// Checks that the timer interrupts arrive, that `Instant` agrees with them, that a busy thread is
+/// preempted with its extended state kept, that tasks can be joined and aborted, that an executor
+/// survives a flood of wakeups, that the async synchronization primitives work, that every CPU
+/// listed in MADT started, and that the memory statistics add up. It also compares the allocation
+/// throughput of the slabs with that of the heap.
+///
+/// If you change the value `0xf4` and `33`, don't forget to change the correspond values in
+/// `Makefile`!
+pub fn run() -> ! {
+    thread::spawn(Priority::Normal, || {
+        let mut executor = Executor::new();
+        executor.spawn(Task::named("join test", join()));
+        executor.spawn(Task::named("sync test", sync()));
+        executor.spawn(Task::named("allocation benchmark", async {
+            benchmark_allocation()
+        }));
+        spawn_stress_tasks(&mut executor);
+        executor.run();
+    });
+
+    let mxcsr = unsafe { _mm_getcsr() };
+    thread::spawn(Priority::Low, || {
+        unsafe { _mm_setcsr(SPINNER_MXCSR) };
+        loop {
+            if unsafe { _mm_getcsr() } != SPINNER_MXCSR {
+                MXCSR_CHANGED.store(true, Ordering::Relaxed);
+            }
+
+            SPINS.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+
+    let start = Instant::now();
+    while time::ticks() < u64::from(time::TICK_HZ / 10) {
+        x86_64::instructions::interrupts::enable_interrupts_and_hlt();
+    }
+    let timer_works = start.elapsed() >= Duration::from_millis(50);
+
+    while !(JOINED.load(Ordering::Relaxed)
+        && STRESSED.load(Ordering::Relaxed)
+        && SYNCED.load(Ordering::Relaxed)
+        && BENCHMARKED.load(Ordering::Relaxed))
+        && start.elapsed() < TIMEOUT
+    {
+        x86_64::instructions::interrupts::enable_interrupts_and_hlt();
+    }
+
+    let num_of_listed_cpus = acpi::madt().map_or(1, |madt| {
+        madt.local_apics()
+            .iter()
+            .filter(|apic| apic.enabled())
+            .count()
+    });
+
+    let qemu = qemu_exit::X86::new(0xf4, 33);
+    if timer_works
+        && smp::num_of_cpus() == num_of_listed_cpus
+        && SPINS.load(Ordering::Relaxed) > 0
+        && !MXCSR_CHANGED.load(Ordering::Relaxed)
+        && unsafe { _mm_getcsr() } == mxcsr
+        && JOINED.load(Ordering::Relaxed)
+        && STRESSED.load(Ordering::Relaxed)
+        && SYNCED.load(Ordering::Relaxed)
+        && BENCHMARKED.load(Ordering::Relaxed)
+        && memory_stats_add_up()
+    {
+        qemu.exit_success();
+    } else {
+        qemu.exit_failure();
+    }
+}
+
+fn memory_stats_add_up() -> bool {
+    let stats = phys::stats();
+    stats.total() > 0 && stats.free() <= stats.total() && stats.used_by_consumers() == stats.used()
+}
+
+async fn join() {
+    let answer = multitask::spawn(Task::new(async { 42 }));
+    let forever = multitask::spawn(Task::new(time::sleep(Duration::from_secs(3600))));
+    forever.abort();
+
+    if answer.await.ok() == Some(42) && forever.await.is_err() {
+        JOINED.store(true, Ordering::Relaxed);
+    }
+}
+
+// Two tasks hold a mutex across `.await` in turn, then report through channels.
+async fn sync() {
+    static COUNTER: Mutex<u32> = Mutex::new(0);
+
+    let (sender, mut receiver) = mpsc::channel(1);
+    let (done_sender, done_receiver) = oneshot::channel();
+
+    for _ in 0..2 {
+        let sender = sender.clone();
+        multitask::spawn(Task::new(async move {
+            let mut counter = COUNTER.lock().await;
+            let value = *counter;
+            time::sleep(Duration::from_millis(1)).await;
+            *counter = value + 1;
+            drop(counter);
+
+            sender.send(value).await.unwrap();
+        }));
+    }
+    drop(sender);
+
+    multitask::spawn(Task::new(async move {
+        let mut sum = 0;
+        while let Some(value) = receiver.recv().await {
+            sum += value;
+        }
+        done_sender.send(sum).unwrap();
+    }));
+
+    if done_receiver.await.ok() == Some(1) && *COUNTER.lock().await == 2 {
+        SYNCED.store(true, Ordering::Relaxed);
+    }
+}
+
+// Allocates and frees the same small object repeatedly, first through the global allocator, which
+// takes it from a slab, then from the heap directly.
+fn benchmark_allocation() {
+    let layout = Layout::from_size_align(BYTES_BENCHMARK_OBJECT, 8).unwrap();
+
+    let slab_time = measure(|| unsafe {
+        let ptr = alloc::alloc::alloc(layout);
+        if !ptr.is_null() {
+            alloc::alloc::dealloc(ptr, layout);
+        }
+        ptr.is_null()
+    });
+    let heap_time = measure(|| unsafe {
+        let ptr = heap::alloc(layout);
+        if !ptr.is_null() {
+            heap::dealloc(ptr, layout);
+        }
+        ptr.is_null()
+    });
+
+    if let (Some(slab_time), Some(heap_time)) = (slab_time, heap_time) {
+        info!(
+            "{} allocations of {} bytes: slab {} us, heap {} us",
+            NUM_OF_BENCHMARK_ALLOCATIONS,
+            BYTES_BENCHMARK_OBJECT,
+            slab_time.as_micros(),
+            heap_time.as_micros()
+        );
+        BENCHMARKED.store(true, Ordering::Relaxed);
+    }
+}
+
+// `f` returns `true` if the allocation fails.
+fn measure(mut f: impl FnMut() -> bool) -> Option<Duration> {
+    let start = Instant::now();
+    for _ in 0..NUM_OF_BENCHMARK_ALLOCATIONS {
+        if f() {
+            return None;
+        }
+    }
+    Some(start.elapsed())
+}
+
+// Every task wakes itself a few times at once, then they pass a baton in turn.
+fn spawn_stress_tasks(executor: &mut Executor) {
+    let batons: Arc<Vec<Baton>> =
+        Arc::new((0..NUM_OF_STRESS_TASKS).map(|_| Baton::new()).collect());
+    batons[0].passed.store(true, Ordering::Relaxed);
+
+    for i in 0..NUM_OF_STRESS_TASKS {
+        let batons = batons.clone();
+        executor.spawn(Task::new(async move {
+            for _ in 0..NUM_OF_YIELDS {
+                YieldNow(false).await;
+            }
+
+            future::poll_fn(|cx| {
+                batons[i].waker.register(cx.waker());
+                if batons[i].passed.load(Ordering::Relaxed) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+
+            match batons.get(i + 1) {
+                Some(next) => {
+                    next.passed.store(true, Ordering::Relaxed);
+                    next.waker.wake();
+                }
+                None => STRESSED.store(true, Ordering::Relaxed),
+            }
+        }));
+    }
+}
+
+struct Baton {
+    passed: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Baton {
+    fn new() -> Self {
+        Self {
+            passed: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
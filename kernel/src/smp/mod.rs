@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod percpu;
+mod trampoline;
+
+use {
+    crate::{
+        acpi, cpu, gdt, idt,
+        interrupt::{self, LocalApic},
+        mem::allocator::stack::Stack,
+        multitask::{executor::Executor, thread},
+        time::{self, Duration, Instant},
+    },
+    core::{
+        convert::TryFrom,
+        hint, mem,
+        sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    spinning_top::{Spinlock, SpinlockGuard},
+    trampoline::Trampoline,
+    x86_64::{instructions::tlb, structures::idt::InterruptStackFrame},
+};
+
+// 64 KiB.
+const ORDER_AP_STACK: usize = 4;
+
+// See Intel SDM Vol. 3A, 8.4.4.1.
+const INIT_DELAY: Duration = Duration::from_millis(10);
+const STARTUP_DELAY: Duration = Duration::from_micros(200);
+const NUM_OF_STARTUP_IPIS: usize = 2;
+const START_TIMEOUT: Duration = Duration::from_millis(100);
+
+static NUM_OF_CPUS: AtomicUsize = AtomicUsize::new(1);
+
+// Set by an application processor when it no longer needs the trampoline.
+static AP_STARTED: AtomicBool = AtomicBool::new(false);
+
+// Held during a TLB shootdown, and while an application processor is started so that every CPU
+// receiving the IPI is counted in `NUM_OF_CPUS`.
+static SHOOTDOWN: Spinlock<()> = Spinlock::new(());
+
+// Incremented by each shootdown. A CPU flushes its TLB once for each value, however many times it
+// checks the value.
+static TLB_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// The CPUs which have not flushed their TLBs for the current shootdown.
+static PENDING_TLB_FLUSHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts the application processors listed in MADT one by one. Each of them runs its own
+/// executor, which takes the tasks spawned by `multitask::spawn`. The timer must be initialized
+/// before calling this.
+pub fn init() {
+    let (local_apic, madt) = match (interrupt::local_apic(), acpi::madt()) {
+        (Some(local_apic), Some(madt)) => (local_apic, madt),
+        _ => {
+            info!("CPUs: 1 (no APIC)");
+            return;
+        }
+    };
+
+    let trampoline = match Trampoline::new() {
+        Some(trampoline) => trampoline,
+        None => {
+            warn!("Cannot place the trampoline. Only the bootstrap processor runs.");
+            return;
+        }
+    };
+
+    let bsp_id = local_apic.id();
+    let aps = madt
+        .local_apics()
+        .iter()
+        .filter(|apic| apic.enabled() && apic.apic_id() != u32::from(bsp_id));
+
+    for ap in aps {
+        match u8::try_from(ap.apic_id()) {
+            Ok(apic_id) => start(local_apic, &trampoline, apic_id),
+            Err(_) => warn!(
+                "APIC ID {} needs x2APIC, which is not supported.",
+                ap.apic_id()
+            ),
+        }
+    }
+
+    drop(trampoline);
+
+    info!("CPUs: {}", num_of_cpus());
+}
+
+/// The number of the running CPUs, including the bootstrap processor.
+pub fn num_of_cpus() -> usize {
+    NUM_OF_CPUS.load(Ordering::Acquire)
+}
+
+/// Makes the other CPUs flush their TLBs and waits until they finish. Call this after unmapping
+/// pages whose virtual addresses may be reused.
+pub fn flush_tlb_of_others() {
+    let _shootdown = lock_shootdown();
+
+    let others = num_of_cpus() - 1;
+    if others == 0 {
+        return;
+    }
+
+    PENDING_TLB_FLUSHES.store(others, Ordering::Release);
+    let generation = TLB_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    // This CPU does not need to flush its own TLB for this shootdown.
+    if let Some(cpu) = percpu::try_current() {
+        cpu.tlb_generation().store(generation, Ordering::Release);
+    }
+    interrupt::send_ipi_to_others(interrupt::VECTOR_TLB_SHOOTDOWN);
+
+    while PENDING_TLB_FLUSHES.load(Ordering::Acquire) != 0 {
+        hint::spin_loop();
+    }
+}
+
+pub extern "x86-interrupt" fn handler_tlb_shootdown(_stack_frame: &mut InterruptStackFrame) {
+    flush_tlb_if_requested();
+
+    if let Some(local_apic) = interrupt::local_apic() {
+        local_apic.end_of_interrupt();
+    }
+}
+
+// The holder of the lock may be waiting for this CPU to flush its TLB, and this CPU may have
+// interrupts disabled. So the request is also checked while spinning.
+fn lock_shootdown() -> SpinlockGuard<'static, ()> {
+    loop {
+        if let Some(guard) = SHOOTDOWN.try_lock() {
+            return guard;
+        }
+
+        flush_tlb_if_requested();
+        hint::spin_loop();
+    }
+}
+
+fn flush_tlb_if_requested() {
+    let cpu = match percpu::try_current() {
+        Some(cpu) => cpu,
+        None => return,
+    };
+
+    let generation = TLB_GENERATION.load(Ordering::Acquire);
+    if cpu.tlb_generation().swap(generation, Ordering::AcqRel) != generation {
+        tlb::flush_all();
+        PENDING_TLB_FLUSHES.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// INIT-SIPI-SIPI. The second startup IPI is only sent if the processor missed the first one.
+fn start(local_apic: &LocalApic, trampoline: &Trampoline, apic_id: u8) {
+    let _shootdown = lock_shootdown();
+    let index = num_of_cpus();
+
+    let stack = match Stack::new(ORDER_AP_STACK) {
+        Some(stack) => stack,
+        None => {
+            warn!("No memory for the stack of CPU with APIC ID {}.", apic_id);
+            return;
+        }
+    };
+
+    // The stack is used by the boot thread of the processor, so it is never freed. Neither is it
+    // freed if the processor is late, as it may start afterwards.
+    let stack_end = stack.end();
+    mem::forget(stack);
+
+    AP_STARTED.store(false, Ordering::Release);
+    trampoline.set_parameters(stack_end, ap_main, index);
+
+    local_apic.send_init(apic_id);
+    time::wait(INIT_DELAY);
+
+    for _ in 0..NUM_OF_STARTUP_IPIS {
+        local_apic.send_startup(apic_id, trampoline.vector());
+        time::wait(STARTUP_DELAY);
+
+        if AP_STARTED.load(Ordering::Acquire) {
+            break;
+        }
+    }
+
+    let start = Instant::now();
+    while !AP_STARTED.load(Ordering::Acquire) {
+        if start.elapsed() >= START_TIMEOUT {
+            // Otherwise the processor might start later with the parameters of the next one.
+            local_apic.send_init(apic_id);
+
+            warn!("CPU with APIC ID {} did not start.", apic_id);
+            return;
+        }
+
+        hint::spin_loop();
+    }
+
+    NUM_OF_CPUS.fetch_add(1, Ordering::AcqRel);
+}
+
+extern "C" fn ap_main(index: usize) -> ! {
+    percpu::init_early();
+    gdt::init_ap();
+    idt::init();
+    percpu::init(index);
+    cpu::extended_state::init();
+    interrupt::init_ap();
+    thread::init();
+    time::init_ap();
+
+    info!("CPU {}: {}", index, cpu::features());
+
+    AP_STARTED.store(true, Ordering::Release);
+
+    Executor::new().run();
+}
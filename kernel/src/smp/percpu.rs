@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{cpu::CpuFeatures, mem::allocator::slab::Magazines, multitask::thread::Scheduler},
+    alloc::boxed::Box,
+    conquer_once::spin::OnceCell,
+    core::{
+        ptr,
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    spinning_top::Spinlock,
+    x86_64::registers::model_specific::Msr,
+};
+
+const MSR_GS_BASE: u32 = 0xc000_0101;
+
+// The GS base points here until `init` is called, so that `gs:0` reads null.
+static NO_PERCPU: u64 = 0;
+
+/// The data each CPU has its own copy of. The GS base of a CPU points to its copy, so it is found
+/// without knowing which CPU the code runs on.
+#[repr(C)]
+pub struct PerCpu {
+    // Must be the first field, as `current` reads it from `gs:0`.
+    this: *const PerCpu,
+    index: usize,
+    features: CpuFeatures,
+    ticks: AtomicU64,
+    scheduler: OnceCell<Spinlock<Scheduler>>,
+    magazines: Magazines,
+    tlb_generation: AtomicU64,
+}
+
+impl PerCpu {
+    /// 0 for the bootstrap processor. The application processors are numbered in the order they
+    /// start.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn apic_id(&self) -> u8 {
+        self.features.apic_id()
+    }
+
+    pub fn features(&self) -> &CpuFeatures {
+        &self.features
+    }
+
+    /// The number of the timer interrupts this CPU has received.
+    pub fn ticks(&self) -> &AtomicU64 {
+        &self.ticks
+    }
+
+    pub fn scheduler(&self) -> &OnceCell<Spinlock<Scheduler>> {
+        &self.scheduler
+    }
+
+    pub fn magazines(&self) -> &Magazines {
+        &self.magazines
+    }
+
+    /// The last TLB shootdown this CPU has flushed its TLB for.
+    pub fn tlb_generation(&self) -> &AtomicU64 {
+        &self.tlb_generation
+    }
+}
+
+/// Makes `try_current` return `None` until `init` is called. This must be called before the first
+/// allocation on this CPU, as the allocator looks for the per-CPU data and the firmware may leave
+/// any value in the GS base.
+pub fn init_early() {
+    unsafe { Msr::new(MSR_GS_BASE).write(&NO_PERCPU as *const u64 as u64) };
+}
+
+/// Allocates the per-CPU data of this CPU and points the GS base to it.
+pub fn init(index: usize) {
+    let percpu = Box::leak(Box::new(PerCpu {
+        this: ptr::null(),
+        index,
+        features: CpuFeatures::detect(),
+        ticks: AtomicU64::new(0),
+        scheduler: OnceCell::uninit(),
+        magazines: Magazines::default(),
+        // No shootdown is in progress while a CPU starts.
+        tlb_generation: AtomicU64::new(super::TLB_GENERATION.load(Ordering::Acquire)),
+    }));
+    percpu.this = percpu;
+
+    unsafe { Msr::new(MSR_GS_BASE).write(percpu.this as u64) };
+}
+
+/// The per-CPU data of this CPU. `init` must be called on this CPU before calling this.
+pub fn current() -> &'static PerCpu {
+    try_current().expect("The per-CPU data is not initialized.")
+}
+
+/// `None` if `init` is not called on this CPU yet. `init_early` must be called before calling this.
+pub fn try_current() -> Option<&'static PerCpu> {
+    let this: *const PerCpu;
+    unsafe {
+        asm!(
+            "mov {}, qword ptr gs:[0]",
+            out(reg) this,
+            options(nostack, preserves_flags, readonly)
+        );
+
+        this.as_ref()
+    }
+}
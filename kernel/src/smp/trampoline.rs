@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{
+        cpu,
+        mem::{
+            allocator::{
+                phys::{Consumer, Zone, FRAME_MANAGER},
+                virt,
+            },
+            paging::pml4::PML4,
+        },
+    },
+    core::{convert::TryFrom, mem, ptr, slice},
+    os_units::Size,
+    x86_64::{
+        registers::{control::Cr3, model_specific::EferFlags},
+        structures::paging::{MapperAllSizes, PageSize, PageTableFlags, PhysFrame, Size4KiB},
+        VirtAddr,
+    },
+};
+
+global_asm!(include_str!("trampoline.s"));
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+}
+
+/// The entry of an application processor. The argument is the one passed to `set_parameters`.
+pub type Entry = extern "C" fn(usize) -> !;
+
+/// The values at the end of the trampoline.
+#[repr(C)]
+struct Parameters {
+    cr3: u64,
+    // The bits set in EFER.
+    efer: u64,
+    stack: u64,
+    entry: u64,
+    argument: u64,
+}
+
+/// The startup code of the application processors, copied to a page below 1 MiB. The page is
+/// identity-mapped, as the code is still running there right after enabling paging.
+pub struct Trampoline {
+    frame: PhysFrame,
+}
+
+impl Trampoline {
+    /// Returns `None` if no page below 1 MiB is available, or if the page tables are above 4 GiB,
+    /// where the trampoline cannot load them before entering long mode.
+    pub fn new() -> Option<Self> {
+        let (pml4, _) = Cr3::read();
+        if pml4.start_address().as_u64() > u64::from(u32::MAX) {
+            return None;
+        }
+
+        let code = code();
+        assert!(
+            code.len() as u64 <= Size4KiB::SIZE,
+            "The trampoline does not fit in a page."
+        );
+
+        let frame = FRAME_MANAGER
+            .lock()
+            .allocate(0, Zone::Below1MiB, Consumer::Other)?;
+        virt::identity_map(
+            frame.start_address(),
+            Size::new(code.len()),
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        );
+
+        // The code is written through the identity mapping.
+        let addr = VirtAddr::new(frame.start_address().as_u64());
+        assert_eq!(
+            PML4.lock().translate_addr(addr),
+            Some(frame.start_address()),
+            "The trampoline page is not identity-mapped."
+        );
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                code.as_ptr(),
+                frame.start_address().as_u64() as *mut u8,
+                code.len(),
+            );
+        }
+
+        Some(Self { frame })
+    }
+
+    /// The vector of the startup IPI, which is the page number of the trampoline.
+    pub fn vector(&self) -> u8 {
+        u8::try_from(self.frame.start_address().as_u64() / Size4KiB::SIZE).unwrap()
+    }
+
+    /// Sets what the next application processor starts with. `stack` is the initial stack
+    /// pointer, which must be 16-byte aligned.
+    pub fn set_parameters(&self, stack: VirtAddr, entry: Entry, argument: usize) {
+        // The kernel maps pages with the NX bit, which is reserved unless NXE is set.
+        let mut efer = EferFlags::LONG_MODE_ENABLE;
+        if cpu::features().nx() {
+            efer |= EferFlags::NO_EXECUTE_ENABLE;
+        }
+
+        let (pml4, _) = Cr3::read();
+        let parameters = Parameters {
+            cr3: pml4.start_address().as_u64(),
+            efer: efer.bits(),
+            stack: stack.as_u64(),
+            entry: entry as usize as u64,
+            argument: argument as u64,
+        };
+
+        let addr = self.frame.start_address().as_u64() + code().len() as u64
+            - mem::size_of::<Parameters>() as u64;
+        unsafe { ptr::write_volatile(addr as *mut Parameters, parameters) };
+    }
+}
+
+// The page is writable and executable, so it must not be left mapped.
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        virt::identity_unmap(self.frame.start_address(), Size::new(code().len()));
+        unsafe {
+            FRAME_MANAGER
+                .lock()
+                .deallocate(self.frame, 0, Consumer::Other)
+        };
+    }
+}
+
+fn code() -> &'static [u8] {
+    unsafe {
+        let start: *const u8 = &ap_trampoline_start;
+        let end: *const u8 = &ap_trampoline_end;
+        slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
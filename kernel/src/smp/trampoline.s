@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// The code an application processor starts with. It is copied to a page below 1 MiB, and the
+// processor starts at the beginning of the page in real mode with CS = page >> 4. The code does
+// not know the address of the page until it runs, so it computes the absolute addresses itself.
+//
+// The parameters at the end are written by the bootstrap processor. Their layout must match
+// `Parameters` in `trampoline.rs`.
+
+.intel_syntax noprefix
+
+.pushsection .rodata.ap_trampoline, "a"
+
+.global ap_trampoline_start
+.global ap_trampoline_end
+
+.code16
+ap_trampoline_start:
+    cli
+    cld
+
+    mov ax, cs
+    mov ds, ax
+
+    // The physical address of the trampoline.
+    xor ebx, ebx
+    mov bx, ax
+    shl ebx, 4
+
+    lea eax, [ebx + trampoline_gdt - ap_trampoline_start]
+    mov [trampoline_gdtr - ap_trampoline_start + 2], eax
+
+    lea eax, [ebx + protected_mode - ap_trampoline_start]
+    mov [trampoline_far_protected_mode - ap_trampoline_start], eax
+
+    lea eax, [ebx + long_mode - ap_trampoline_start]
+    mov [trampoline_far_long_mode - ap_trampoline_start], eax
+
+    lgdt [trampoline_gdtr - ap_trampoline_start]
+
+    mov eax, cr0
+    or eax, 1 // PE
+    mov cr0, eax
+
+    // jmp fword ptr [trampoline_far_protected_mode - ap_trampoline_start]
+    .byte 0x66, 0xff, 0x2e
+    .word trampoline_far_protected_mode - ap_trampoline_start
+
+.code32
+protected_mode:
+    mov ax, 0x10
+    mov ds, ax
+    mov es, ax
+    mov ss, ax
+
+    // The Rust code may use SSE before enabling the rest of the extended state.
+    mov eax, cr4
+    or eax, (1 << 5) | (1 << 9) | (1 << 10) // PAE | OSFXSR | OSXMMEXCPT
+    mov cr4, eax
+
+    // The page tables of the bootstrap processor. They must be below 4 GiB.
+    mov eax, [ebx + trampoline_cr3 - ap_trampoline_start]
+    mov cr3, eax
+
+    // LME, and NXE if the CPU supports it.
+    mov ecx, 0xc0000080 // EFER
+    rdmsr
+    or eax, [ebx + trampoline_efer - ap_trampoline_start]
+    wrmsr
+
+    // INIT disables the caches, so enable them again.
+    mov eax, cr0
+    and eax, ~((1 << 30) | (1 << 29) | (1 << 2)) // CD | NW | EM
+    or eax, (1 << 31) | (1 << 16) | (1 << 1) // PG | WP | MP
+    mov cr0, eax
+
+    // jmp fword ptr [ebx + trampoline_far_long_mode - ap_trampoline_start]
+    .byte 0xff, 0xab
+    .long trampoline_far_long_mode - ap_trampoline_start
+
+.code64
+long_mode:
+    // The upper half of rbx is undefined after the mode switch.
+    mov ebx, ebx
+
+    mov rsp, [rbx + trampoline_stack - ap_trampoline_start]
+    mov rdi, [rbx + trampoline_argument - ap_trampoline_start]
+    mov rax, [rbx + trampoline_entry - ap_trampoline_start]
+    call rax
+
+    // The entry never returns.
+    ud2
+
+.balign 8
+trampoline_gdt:
+    .quad 0
+    .quad 0x00cf9a000000ffff // 32-bit code
+    .quad 0x00cf92000000ffff // Data
+    .quad 0x00af9a000000ffff // 64-bit code
+
+trampoline_gdtr:
+    .word 4 * 8 - 1
+    .long 0
+
+trampoline_far_protected_mode:
+    .long 0
+    .word 0x08
+
+trampoline_far_long_mode:
+    .long 0
+    .word 0x18
+
+.balign 8
+trampoline_cr3:
+    .quad 0
+trampoline_efer:
+    .quad 0
+trampoline_stack:
+    .quad 0
+trampoline_entry:
+    .quad 0
+trampoline_argument:
+    .quad 0
+ap_trampoline_end:
+
+.popsection
+.att_syntax prefix
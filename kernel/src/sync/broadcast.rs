@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{next_waiter_id, IrqSpinlock},
+    alloc::{collections::VecDeque, sync::Arc, vec::Vec},
+    core::{
+        convert::TryFrom,
+        task::{Context, Poll, Waker},
+    },
+    futures_util::future,
+};
+
+/// Creates a channel which delivers every value to every receiver. It keeps the latest `capacity`
+/// values, and a receiver which falls further behind skips the older ones.
+///
+/// # Panics
+///
+/// This function panics if `capacity` is 0.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "The capacity must not be 0.");
+
+    let shared = Arc::new(IrqSpinlock::new(Shared {
+        values: VecDeque::with_capacity(capacity),
+        capacity,
+        head: 0,
+        senders: 1,
+        receivers: 1,
+        waiters: Vec::new(),
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver {
+            shared,
+            next: 0,
+            id: next_waiter_id(),
+        },
+    )
+}
+
+struct Shared<T> {
+    values: VecDeque<T>,
+    capacity: usize,
+    // The sequence number of `values[0]`.
+    head: u64,
+    senders: usize,
+    receivers: usize,
+    waiters: Vec<(u64, Waker)>,
+}
+
+impl<T> Shared<T> {
+    fn tail(&self) -> u64 {
+        self.head + u64::try_from(self.values.len()).unwrap()
+    }
+
+    fn wake_all(&mut self) {
+        for (_, waker) in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<IrqSpinlock<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Returns the number of receivers, or gives `value` back if there is none. This never
+    /// allocates, so it can be called from interrupt handlers.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let mut shared = self.shared.lock();
+        if shared.receivers == 0 {
+            return Err(SendError(value));
+        }
+
+        if shared.values.len() == shared.capacity {
+            shared.values.pop_front();
+            shared.head += 1;
+        }
+        shared.values.push_back(value);
+        shared.wake_all();
+
+        Ok(shared.receivers)
+    }
+
+    /// Creates a receiver which gets the values sent after this call.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut shared = self.shared.lock();
+        shared.receivers += 1;
+
+        Receiver {
+            shared: self.shared.clone(),
+            next: shared.tail(),
+            id: next_waiter_id(),
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wake_all();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<IrqSpinlock<Shared<T>>>,
+    // The sequence number of the value to receive next.
+    next: u64,
+    id: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<Result<T, RecvError>> {
+        let mut shared = self.shared.lock();
+
+        if self.next < shared.head {
+            let skipped = shared.head - self.next;
+            self.next = shared.head;
+            return Poll::Ready(Err(RecvError::Lagged(skipped)));
+        }
+
+        if self.next < shared.tail() {
+            let i = usize::try_from(self.next - shared.head).unwrap();
+            self.next += 1;
+            return Poll::Ready(Ok(shared.values[i].clone()));
+        }
+
+        if shared.senders == 0 {
+            return Poll::Ready(Err(RecvError::Closed));
+        }
+
+        let id = self.id;
+        shared.waiters.retain(|(waiter, _)| *waiter != id);
+        shared.waiters.push((id, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock();
+        shared.receivers -= 1;
+
+        let id = self.id;
+        shared.waiters.retain(|(waiter, _)| *waiter != id);
+    }
+}
+
+#[derive(Debug)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug)]
+pub enum RecvError {
+    /// The receiver fell behind, and this many values were skipped.
+    Lagged(u64),
+    Closed,
+}
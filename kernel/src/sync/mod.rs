@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Unlike spinlocks, the async primitives can be held across `.await`, and waiting tasks sleep until
+// they are woken.
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+mod spinlock;
+
+pub use {
+    mutex::{Mutex, MutexGuard},
+    notify::{Notified, Notify},
+    rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    semaphore::{Acquire, Closed, Semaphore, SemaphorePermit},
+    spinlock::{IrqSpinlock, IrqSpinlockGuard},
+};
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Identifies a waiting future, so that it can remove itself from a wait list when dropped.
+fn next_waiter_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{IrqSpinlock, Semaphore},
+    alloc::{collections::VecDeque, sync::Arc},
+    core::{
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    },
+    futures_util::{future, stream::Stream},
+};
+
+/// Creates a channel holding up to `capacity` values. The buffer is allocated here, so `try_send`
+/// never allocates and can be called from interrupt handlers.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        state: IrqSpinlock::new(State {
+            values: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receiver_waker: None,
+        }),
+        capacity: Semaphore::new(capacity),
+    });
+
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+struct Chan<T> {
+    state: IrqSpinlock<State<T>>,
+    // The free slots of the buffer. Closed when the receiver is dropped.
+    capacity: Semaphore,
+}
+
+impl<T> Chan<T> {
+    fn push(&self, value: T) {
+        let mut state = self.state.lock();
+        state.values.push_back(value);
+        if let Some(waker) = state.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct State<T> {
+    values: VecDeque<T>,
+    senders: usize,
+    receiver_waker: Option<Waker>,
+}
+
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Waits for a free slot. Gives `value` back if the receiver is dropped.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.chan.capacity.acquire(1).await {
+            Ok(permit) => {
+                permit.forget();
+                self.chan.push(value);
+                Ok(())
+            }
+            Err(_) => Err(SendError(value)),
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self.chan.capacity.try_acquire(1) {
+            Some(permit) => {
+                permit.forget();
+                self.chan.push(value);
+                Ok(())
+            }
+            None if self.chan.capacity.is_closed() => Err(TrySendError::Closed(value)),
+            None => Err(TrySendError::Full(value)),
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.state.lock().senders += 1;
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Returns `None` once all the senders are dropped and the buffer is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        let mut state = self.chan.state.lock();
+        if let Some(value) = state.values.pop_front() {
+            drop(state);
+            self.chan.capacity.add_permits(1);
+            Poll::Ready(Some(value))
+        } else if state.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            state.receiver_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.capacity.close();
+    }
+}
+
+#[derive(Debug)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{next_waiter_id, IrqSpinlock},
+    alloc::vec::Vec,
+    core::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    },
+};
+
+/// Wakes waiting tasks without carrying data. Notifying never allocates, so it can be done from
+/// interrupt handlers.
+pub struct Notify {
+    state: IrqSpinlock<State>,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Self {
+            state: IrqSpinlock::new(State {
+                permit: false,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            id: None,
+        }
+    }
+
+    /// Wakes the task which has waited longest. If nobody is waiting, the next `notified` completes
+    /// immediately.
+    pub fn notify_one(&self) {
+        self.state.lock().notify_one();
+    }
+
+    /// Wakes all the tasks waiting now. This does not affect later `notified` calls.
+    pub fn notify_waiters(&self) {
+        for waiter in &mut self.state.lock().waiters {
+            if waiter.notification.is_none() {
+                waiter.notification = Some(Notification::All);
+                waiter.waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+struct State {
+    permit: bool,
+    waiters: Vec<Waiter>,
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        match self
+            .waiters
+            .iter_mut()
+            .find(|waiter| waiter.notification.is_none())
+        {
+            Some(waiter) => {
+                waiter.notification = Some(Notification::One);
+                waiter.waker.wake_by_ref();
+            }
+            None => self.permit = true,
+        }
+    }
+}
+
+struct Waiter {
+    id: u64,
+    waker: Waker,
+    notification: Option<Notification>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Notification {
+    One,
+    All,
+}
+
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    // Set while in the wait list.
+    id: Option<u64>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        let mut state = this.notify.state.lock();
+
+        let id = match this.id {
+            Some(id) => id,
+            None => {
+                if state.permit {
+                    state.permit = false;
+                    return Poll::Ready(());
+                }
+
+                let id = next_waiter_id();
+                this.id = Some(id);
+                state.waiters.push(Waiter {
+                    id,
+                    waker: cx.waker().clone(),
+                    notification: None,
+                });
+                return Poll::Pending;
+            }
+        };
+
+        let i = state
+            .waiters
+            .iter()
+            .position(|waiter| waiter.id == id)
+            .expect("A waiter is removed from the list.");
+
+        if state.waiters[i].notification.is_some() {
+            state.waiters.remove(i);
+            this.id = None;
+            Poll::Ready(())
+        } else {
+            if !state.waiters[i].waker.will_wake(cx.waker()) {
+                state.waiters[i].waker = cx.waker().clone();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut state = self.notify.state.lock();
+            if let Some(i) = state.waiters.iter().position(|waiter| waiter.id == id) {
+                // A notification for one waiter must not be lost.
+                if state.waiters.remove(i).notification == Some(Notification::One) {
+                    state.notify_one();
+                }
+            }
+        }
+    }
+}
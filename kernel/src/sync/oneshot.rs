@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::IrqSpinlock,
+    alloc::sync::Arc,
+    core::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    },
+};
+
+/// Creates a channel which sends a single value. Sending never allocates, so it can be done from
+/// interrupt handlers.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(IrqSpinlock::new(Shared {
+        value: None,
+        sender_alive: true,
+        receiver_alive: true,
+        waker: None,
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<T> {
+    value: Option<T>,
+    sender_alive: bool,
+    receiver_alive: bool,
+    waker: Option<Waker>,
+}
+
+pub struct Sender<T> {
+    shared: Arc<IrqSpinlock<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Gives `value` back if the receiver is dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut shared = self.shared.lock();
+        if !shared.receiver_alive {
+            return Err(value);
+        }
+
+        shared.value = Some(value);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock();
+        shared.sender_alive = false;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Completes with the value, or with `RecvError` if the sender is dropped without sending.
+pub struct Receiver<T> {
+    shared: Arc<IrqSpinlock<Shared<T>>>,
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock();
+        if let Some(value) = shared.value.take() {
+            Poll::Ready(Ok(value))
+        } else if shared.sender_alive {
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(Err(RecvError))
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_alive = false;
+    }
+}
+
+#[derive(Debug)]
+pub struct RecvError;
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{next_waiter_id, IrqSpinlock},
+    alloc::vec::Vec,
+    core::{
+        future::Future,
+        mem,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    },
+};
+
+/// An async counting semaphore. Waiters acquire permits in the order they started waiting, so a
+/// large request is not starved by small ones.
+pub struct Semaphore {
+    state: IrqSpinlock<State>,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            state: IrqSpinlock::new(State {
+                permits,
+                closed: false,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    /// Waits for `n` permits. Fails if the semaphore is closed.
+    pub fn acquire(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            n,
+            id: None,
+        }
+    }
+
+    /// Acquires `n` permits if they are available now and nobody is waiting for them.
+    pub fn try_acquire(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.state.lock();
+        if state.closed || !state.waiters.is_empty() || state.permits < n {
+            return None;
+        }
+
+        state.permits -= n;
+        Some(SemaphorePermit { semaphore: self, n })
+    }
+
+    /// This never allocates, so it can be called from interrupt handlers.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock();
+        state.permits += n;
+        state.wake_first();
+    }
+
+    /// Fails all the current and future `acquire` calls.
+    pub fn close(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        for waiter in &state.waiters {
+            waiter.waker.wake_by_ref();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+}
+
+#[derive(Debug)]
+pub struct Closed;
+
+struct State {
+    permits: usize,
+    closed: bool,
+    waiters: Vec<Waiter>,
+}
+
+impl State {
+    fn wake_first(&self) {
+        if let Some(waiter) = self.waiters.first() {
+            waiter.waker.wake_by_ref();
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        let was_first = self.waiters.first().map(|waiter| waiter.id) == Some(id);
+        self.waiters.retain(|waiter| waiter.id != id);
+
+        // The permits the removed waiter was waiting for may satisfy the next one.
+        if was_first {
+            self.wake_first();
+        }
+    }
+}
+
+struct Waiter {
+    id: u64,
+    waker: Waker,
+}
+
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    n: usize,
+    // Set while in the wait list.
+    id: Option<u64>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a>, Closed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.semaphore.state.lock();
+
+        if state.closed {
+            if let Some(id) = this.id.take() {
+                state.remove(id);
+            }
+            return Poll::Ready(Err(Closed));
+        }
+
+        let is_first = state.waiters.first().map(|waiter| waiter.id) == this.id;
+        if (state.waiters.is_empty() || is_first) && state.permits >= this.n {
+            state.permits -= this.n;
+            if let Some(id) = this.id.take() {
+                state.remove(id);
+            }
+
+            return Poll::Ready(Ok(SemaphorePermit {
+                semaphore: this.semaphore,
+                n: this.n,
+            }));
+        }
+
+        match this.id {
+            Some(id) => {
+                if let Some(waiter) = state.waiters.iter_mut().find(|waiter| waiter.id == id) {
+                    if !waiter.waker.will_wake(cx.waker()) {
+                        waiter.waker = cx.waker().clone();
+                    }
+                }
+            }
+            None => {
+                let id = next_waiter_id();
+                this.id = Some(id);
+                state.waiters.push(Waiter {
+                    id,
+                    waker: cx.waker().clone(),
+                });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.semaphore.state.lock().remove(id);
+        }
+    }
+}
+
+/// Returns the permits when dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    n: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Keeps the permits out of the semaphore. They can be returned with `add_permits`.
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.n);
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    core::{
+        mem::ManuallyDrop,
+        ops::{Deref, DerefMut},
+    },
+    spinning_top::{Spinlock, SpinlockGuard},
+    x86_64::instructions::interrupts,
+};
+
+/// A spinlock which disables interrupts while it is held. Use this for data shared with interrupt
+/// handlers, as an interrupt handler spinning on a lock held by the code it interrupted never gets
+/// the lock.
+pub struct IrqSpinlock<T> {
+    inner: Spinlock<T>,
+}
+
+impl<T> IrqSpinlock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: Spinlock::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+        let were_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        IrqSpinlockGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            were_enabled,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that the holder of the lock no longer accesses the data.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock();
+    }
+}
+
+/// Enables interrupts again when dropped, if they were enabled before locking.
+pub struct IrqSpinlockGuard<'a, T> {
+    guard: ManuallyDrop<SpinlockGuard<'a, T>>,
+    were_enabled: bool,
+}
+
+impl<T> Deref for IrqSpinlockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqSpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for IrqSpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        // The lock must be released before enabling interrupts.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+
+        if self.were_enabled {
+            interrupts::enable();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    crate::{acpi, mem::allocator::virt},
+    core::{convert::TryFrom, ptr, time::Duration},
+    os_units::Size,
+    x86_64::{PhysAddr, VirtAddr},
+};
+
+// See IA-PC HPET Specification 1.0a, 2.3.
+const OFFSET_CAPABILITIES: u64 = 0x000;
+const OFFSET_CONFIG: u64 = 0x010;
+const OFFSET_MAIN_COUNTER: u64 = 0x0f0;
+const OFFSET_TIMER0_CONFIG: u64 = 0x100;
+const OFFSET_TIMER0_COMPARATOR: u64 = 0x108;
+const BYTES_REGISTERS: usize = 0x400;
+
+const CONFIG_ENABLE: u64 = 1 << 0;
+const CONFIG_LEGACY_REPLACEMENT: u64 = 1 << 1;
+
+const TIMER_INTERRUPT_ENABLE: u64 = 1 << 2;
+const TIMER_PERIODIC: u64 = 1 << 3;
+const TIMER_PERIODIC_CAPABLE: u64 = 1 << 4;
+const TIMER_SET_ACCUMULATOR: u64 = 1 << 6;
+
+const FEMTOSECONDS_PER_SECOND: u128 = 1_000_000_000_000_000;
+
+pub struct Hpet {
+    base: VirtAddr,
+    period_fs: u64,
+    counter_is_64bit: bool,
+    legacy_replacement: bool,
+}
+
+impl Hpet {
+    /// Maps the registers and starts the main counter.
+    pub fn new(table: &acpi::hpet::Hpet) -> Self {
+        let base = virt::map_mmio(
+            PhysAddr::new(table.base().address()),
+            Size::new(BYTES_REGISTERS),
+        );
+
+        let mut hpet = Self {
+            base,
+            period_fs: 0,
+            counter_is_64bit: table.counter_is_64bit(),
+            legacy_replacement: table.legacy_replacement(),
+        };
+
+        hpet.period_fs = hpet.read(OFFSET_CAPABILITIES) >> 32;
+
+        let config = hpet.read(OFFSET_CONFIG);
+        hpet.write(OFFSET_CONFIG, config | CONFIG_ENABLE);
+
+        hpet
+    }
+
+    /// Whether timer 0 can raise IRQ 0 periodically.
+    pub fn supports_periodic(&self) -> bool {
+        self.legacy_replacement && self.read(OFFSET_TIMER0_CONFIG) & TIMER_PERIODIC_CAPABLE != 0
+    }
+
+    /// Raises IRQ 0 `hz` times per second with timer 0. The legacy replacement route disconnects
+    /// the PIT from IRQ 0.
+    pub fn start_periodic(&self, hz: u32) {
+        let period =
+            u64::try_from(FEMTOSECONDS_PER_SECOND / u128::from(hz) / u128::from(self.period_fs))
+                .expect("The frequency is too low.");
+
+        let config = self.read(OFFSET_CONFIG);
+        self.write(OFFSET_CONFIG, config & !CONFIG_ENABLE);
+
+        let timer = self.read(OFFSET_TIMER0_CONFIG);
+        self.write(
+            OFFSET_TIMER0_CONFIG,
+            timer | TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC | TIMER_SET_ACCUMULATOR,
+        );
+        self.write(OFFSET_TIMER0_COMPARATOR, self.counter() + period);
+        // The second write sets the period.
+        self.write(OFFSET_TIMER0_COMPARATOR, period);
+
+        self.write(
+            OFFSET_CONFIG,
+            config | CONFIG_ENABLE | CONFIG_LEGACY_REPLACEMENT,
+        );
+    }
+
+    pub fn wait(&self, duration: Duration) {
+        let counts = u64::try_from(duration.as_nanos() * 1_000_000 / u128::from(self.period_fs))
+            .expect("The duration is too long.");
+
+        let start = self.counter();
+        while self.elapsed_since(start) < counts {}
+    }
+
+    fn counter(&self) -> u64 {
+        self.read(OFFSET_MAIN_COUNTER)
+    }
+
+    // A 32-bit counter wraps around in about 5 minutes at 14.318 MHz.
+    fn elapsed_since(&self, start: u64) -> u64 {
+        let elapsed = self.counter().wrapping_sub(start);
+        if self.counter_is_64bit {
+            elapsed
+        } else {
+            elapsed & u64::from(u32::MAX)
+        }
+    }
+
+    fn read(&self, offset: u64) -> u64 {
+        unsafe { ptr::read_volatile((self.base + offset).as_ptr()) }
+    }
+
+    fn write(&self, offset: u64, value: u64) {
+        unsafe { ptr::write_volatile((self.base + offset).as_mut_ptr(), value) }
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::CALIBRATION_PERIOD,
+    core::{
+        arch::x86_64::_rdtsc,
+        convert::TryFrom,
+        ops::{Add, Sub},
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+};
+
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// A point of the monotonic time, measured with the time stamp counter. The TSC is assumed to be
+/// invariant and synchronized among the CPUs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub fn now() -> Self {
+        Self(rdtsc())
+    }
+
+    /// Returns zero if `earlier` is later than `self`.
+    pub fn duration_since(self, earlier: Self) -> Duration {
+        let cycles = u128::from(self.0.saturating_sub(earlier.0));
+        let hz = u128::from(tsc_hz());
+
+        Duration::new(
+            u64::try_from(cycles / hz).unwrap(),
+            u32::try_from(cycles % hz * NANOSECONDS_PER_SECOND / hz).unwrap(),
+        )
+    }
+
+    pub fn elapsed(self) -> Duration {
+        Self::now().duration_since(self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Self;
+
+    fn add(self, rhs: Duration) -> Self {
+        let cycles = rhs.as_nanos() * u128::from(tsc_hz()) / NANOSECONDS_PER_SECOND;
+        Self(self.0 + u64::try_from(cycles).expect("The duration is too long."))
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+pub(super) fn calibrate() {
+    let start = rdtsc();
+    super::wait(CALIBRATION_PERIOD);
+    let end = rdtsc();
+
+    TSC_HZ.store(super::per_second(end - start), Ordering::Relaxed);
+}
+
+pub(super) fn tsc_hz() -> u64 {
+    let hz = TSC_HZ.load(Ordering::Relaxed);
+    assert_ne!(hz, 0, "The TSC is not calibrated.");
+    hz
+}
+
+pub(super) fn rdtsc() -> u64 {
+    unsafe { _rdtsc() }
+}
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+mod hpet;
+mod instant;
+mod pit;
+mod system;
+mod timer;
+
+pub use {
+    core::time::Duration,
+    instant::Instant,
+    system::{set_wall_clock, DateTime, SystemTime},
+    timer::{interval, sleep, timeout},
+};
+
+use {
+    crate::{
+        acpi, cpu,
+        interrupt::{self, irq, LocalApic},
+        multitask::thread,
+        smp::percpu,
+    },
+    conquer_once::spin::OnceCell,
+    core::{
+        convert::TryFrom,
+        sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    hpet::Hpet,
+    x86_64::structures::idt::InterruptStackFrame,
+};
+
+pub const TICK_HZ: u32 = 100;
+
+const IRQ_TIMER: u8 = 0;
+
+const CALIBRATION_PERIOD: Duration = Duration::from_millis(10);
+
+static HPET: OnceCell<Hpet> = OnceCell::uninit();
+
+// The count of the local APIC timer per tick, shared with the application processors. 0 if the
+// periodic mode of the local APIC timer is not used.
+static LOCAL_APIC_TIMER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+// The TSC cycles per tick if the local APIC timer is in the TSC-deadline mode, or 0 otherwise.
+static TSC_DEADLINE_PERIOD: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+enum Source {
+    TscDeadline,
+    LocalApic,
+    Hpet,
+    Pit,
+}
+
+/// Calibrates the TSC and starts the periodic tick. The interrupt controller must be initialized
+/// before calling this.
+pub fn init() {
+    if let Some(table) = acpi::hpet() {
+        HPET.try_init_once(|| Hpet::new(table))
+            .expect("HPET is already initialized.");
+    }
+
+    instant::calibrate();
+
+    let source = start_tick();
+    info!(
+        "Timer: {:?} at {} Hz, TSC: {} MHz",
+        source,
+        TICK_HZ,
+        instant::tsc_hz() / 1_000_000
+    );
+}
+
+/// Starts the tick of an application processor in the same mode as the bootstrap processor, with
+/// the values it calibrated.
+pub fn init_ap() {
+    if let Some(local_apic) = interrupt::local_apic() {
+        if TSC_DEADLINE_PERIOD.load(Ordering::Relaxed) == 0 {
+            local_apic.start_periodic_timer(
+                interrupt::VECTOR_LOCAL_TIMER,
+                LOCAL_APIC_TIMER_COUNT.load(Ordering::Relaxed),
+            );
+        } else {
+            local_apic.start_tsc_deadline_timer(interrupt::VECTOR_LOCAL_TIMER);
+            set_next_deadline(local_apic);
+        }
+    }
+}
+
+/// The number of ticks this CPU has received.
+pub fn ticks() -> u64 {
+    percpu::current().ticks().load(Ordering::Relaxed)
+}
+
+pub extern "x86-interrupt" fn handler_local_timer(_stack_frame: &mut InterruptStackFrame) {
+    tick();
+
+    if let Some(local_apic) = interrupt::local_apic() {
+        if TSC_DEADLINE_PERIOD.load(Ordering::Relaxed) != 0 {
+            set_next_deadline(local_apic);
+        }
+
+        local_apic.end_of_interrupt();
+    }
+
+    thread::preempt_if_requested();
+}
+
+// The local APIC timer is preferred as each CPU has its own. The TSC-deadline mode uses the
+// calibrated TSC, so the local APIC timer itself needs no calibration. HPET and the PIT have known
+// frequencies, so they need no calibration either.
+fn start_tick() -> Source {
+    if let Some(local_apic) = interrupt::local_apic() {
+        if cpu::features().tsc_deadline() {
+            TSC_DEADLINE_PERIOD.store(instant::tsc_hz() / u64::from(TICK_HZ), Ordering::Relaxed);
+            local_apic.start_tsc_deadline_timer(interrupt::VECTOR_LOCAL_TIMER);
+            set_next_deadline(local_apic);
+
+            return Source::TscDeadline;
+        }
+
+        local_apic.start_timer_measurement();
+        wait(CALIBRATION_PERIOD);
+        let hz = per_second(u64::from(local_apic.timer_elapsed()));
+
+        let count =
+            u32::try_from(hz / u64::from(TICK_HZ)).expect("The local APIC timer is too fast.");
+        LOCAL_APIC_TIMER_COUNT.store(count, Ordering::Relaxed);
+        local_apic.start_periodic_timer(interrupt::VECTOR_LOCAL_TIMER, count);
+
+        Source::LocalApic
+    } else if let Some(hpet) = HPET.try_get().ok().filter(|hpet| hpet.supports_periodic()) {
+        irq::register(IRQ_TIMER, tick);
+        hpet.start_periodic(TICK_HZ);
+
+        Source::Hpet
+    } else {
+        irq::register(IRQ_TIMER, tick);
+        pit::start_periodic(TICK_HZ);
+
+        Source::Pit
+    }
+}
+
+// The deadline is counted from now rather than from the previous one, so that a late interrupt
+// does not cause a burst of ticks.
+fn set_next_deadline(local_apic: &LocalApic) {
+    local_apic.set_tsc_deadline(instant::rdtsc() + TSC_DEADLINE_PERIOD.load(Ordering::Relaxed));
+}
+
+fn tick() {
+    percpu::current().ticks().fetch_add(1, Ordering::Relaxed);
+
+    timer::wake_expired();
+    thread::tick();
+}
+
+/// Busy-waits with HPET if available, or with the PIT otherwise.
+pub fn wait(duration: Duration) {
+    match HPET.try_get() {
+        Ok(hpet) => hpet.wait(duration),
+        Err(_) => pit::wait(duration),
+    }
+}
+
+/// Converts the counts during `CALIBRATION_PERIOD` into the counts per second.
+fn per_second(counts: u64) -> u64 {
+    u64::try_from(u128::from(counts) * 1_000_000_000 / CALIBRATION_PERIOD.as_nanos()).unwrap()
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    core::{convert::TryFrom, time::Duration},
+    x86_64::instructions::port::Port,
+};
+
+// See Intel 8254 Programmable Interval Timer datasheet.
+const FREQUENCY: u64 = 1_193_182;
+
+const PORT_CHANNEL0: u16 = 0x40;
+const PORT_CHANNEL2: u16 = 0x42;
+const PORT_COMMAND: u16 = 0x43;
+const PORT_CHANNEL2_GATE: u16 = 0x61;
+
+const COMMAND_CHANNEL0_RATE_GENERATOR: u8 = 0b0011_0100;
+const COMMAND_CHANNEL2_ONE_SHOT: u8 = 0b1011_0000;
+
+const GATE_ENABLE: u8 = 1 << 0;
+const GATE_SPEAKER: u8 = 1 << 1;
+const GATE_OUTPUT: u8 = 1 << 5;
+
+/// Raises IRQ 0 `hz` times per second.
+pub fn start_periodic(hz: u32) {
+    let divisor = u16::try_from(FREQUENCY / u64::from(hz)).expect("The frequency is too low.");
+
+    unsafe {
+        Port::new(PORT_COMMAND).write(COMMAND_CHANNEL0_RATE_GENERATOR);
+        write_count(PORT_CHANNEL0, divisor);
+    }
+}
+
+/// Busy-waits with channel 2, which does not raise an interrupt.
+pub fn wait(duration: Duration) {
+    let mut remaining = u64::try_from(duration.as_nanos() * u128::from(FREQUENCY) / 1_000_000_000)
+        .expect("The duration is too long.");
+
+    while remaining > 0 {
+        let count = remaining.min(u64::from(u16::MAX));
+        wait_counts(u16::try_from(count).unwrap());
+        remaining -= count;
+    }
+}
+
+fn wait_counts(count: u16) {
+    let mut gate = Port::<u8>::new(PORT_CHANNEL2_GATE);
+
+    unsafe {
+        let value = gate.read() & !(GATE_ENABLE | GATE_SPEAKER);
+        gate.write(value);
+
+        Port::new(PORT_COMMAND).write(COMMAND_CHANNEL2_ONE_SHOT);
+        write_count(PORT_CHANNEL2, count);
+
+        // The count starts on the rising edge of the gate.
+        gate.write(value | GATE_ENABLE);
+
+        while gate.read() & GATE_OUTPUT == 0 {}
+
+        gate.write(value);
+    }
+}
+
+unsafe fn write_count(port: u16, count: u16) {
+    let [low, high] = count.to_le_bytes();
+    let mut port = Port::<u8>::new(port);
+    port.write(low);
+    port.write(high);
+}
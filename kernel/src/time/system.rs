@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{Duration, Instant},
+    conquer_once::spin::OnceCell,
+    core::{convert::TryFrom, fmt},
+};
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+// The days from 0000-03-01 to 1970-01-01 in the proleptic Gregorian calendar.
+const DAYS_TO_UNIX_EPOCH: u64 = 719_468;
+const DAYS_PER_400_YEARS: u64 = 146_097;
+
+/// The wall-clock time at an instant.
+static WALL_CLOCK: OnceCell<(SystemTime, Instant)> = OnceCell::uninit();
+
+/// The wall-clock time in UTC, measured from the UNIX epoch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SystemTime(Duration);
+
+impl SystemTime {
+    /// # Panics
+    ///
+    /// This method panics if the wall clock is not set.
+    pub fn now() -> Self {
+        let (time, instant) = WALL_CLOCK.try_get().expect("The wall clock is not set.");
+        Self(time.0 + instant.elapsed())
+    }
+}
+
+impl From<DateTime> for SystemTime {
+    fn from(date_time: DateTime) -> Self {
+        let days = days_from_civil(date_time.year, date_time.month, date_time.day);
+        let seconds = days * SECONDS_PER_DAY
+            + u64::from(date_time.hour) * 3600
+            + u64::from(date_time.minute) * 60
+            + u64::from(date_time.second);
+
+        Self(Duration::from_secs(seconds))
+    }
+}
+
+/// A date and a time in UTC. Years before 1970 are not supported.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    const UNIX_EPOCH: Self = Self {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+
+    // A broken or reset RTC may return values out of these ranges.
+    fn is_valid(&self) -> bool {
+        self.year >= 1970
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let seconds = time.0.as_secs();
+        let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+        let seconds_of_day = seconds % SECONDS_PER_DAY;
+
+        Self {
+            year,
+            month,
+            day,
+            hour: u8::try_from(seconds_of_day / 3600).unwrap(),
+            minute: u8::try_from(seconds_of_day / 60 % 60).unwrap(),
+            second: u8::try_from(seconds_of_day % 60).unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Sets the wall clock. It is advanced by the monotonic clock afterwards. An invalid date and time
+/// is replaced with the UNIX epoch.
+pub fn set_wall_clock(now: DateTime) {
+    let now = if now.is_valid() {
+        now
+    } else {
+        warn!(
+            "Invalid date and time: {}. Starting from the UNIX epoch.",
+            now
+        );
+        DateTime::UNIX_EPOCH
+    };
+
+    WALL_CLOCK
+        .try_init_once(|| (SystemTime::from(now), Instant::now()))
+        .expect("The wall clock is already set.");
+}
+
+// See http://howardhinnant.github.io/date_algorithms.html. Years start from March so that the leap
+// day comes last. The date must be valid.
+fn days_from_civil(year: u16, month: u8, day: u8) -> u64 {
+    let year = u64::from(year) - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (u64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + u64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * DAYS_PER_400_YEARS + day_of_era - DAYS_TO_UNIX_EPOCH
+}
+
+fn civil_from_days(days: u64) -> (u16, u8, u8) {
+    let days = days + DAYS_TO_UNIX_EPOCH;
+    let era = days / DAYS_PER_400_YEARS;
+    let day_of_era = days - era * DAYS_PER_400_YEARS;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (
+        u16::try_from(year).unwrap(),
+        u8::try_from(month).unwrap(),
+        u8::try_from(day).unwrap(),
+    )
+}
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use {
+    super::{Duration, Instant},
+    crate::sync::IrqSpinlock,
+    alloc::collections::BTreeMap,
+    conquer_once::spin::Lazy,
+    core::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicU64, Ordering},
+        task::{Context, Poll, Waker},
+    },
+    futures_util::stream::Stream,
+    pin_project_lite::pin_project,
+};
+
+// The timer interrupt only marks entries as fired, so that it never allocates or frees memory.
+static TIMERS: Lazy<IrqSpinlock<BTreeMap<Key, Entry>>> =
+    Lazy::new(|| IrqSpinlock::new(BTreeMap::new()));
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    deadline: Instant,
+    id: u64,
+}
+
+struct Entry {
+    waker: Waker,
+    fired: bool,
+}
+
+/// Wakes the tasks whose deadlines have passed. Called from the timer interrupt.
+pub(super) fn wake_expired() {
+    let now = Key {
+        deadline: Instant::now(),
+        id: u64::MAX,
+    };
+
+    for entry in TIMERS.lock().range_mut(..=now).map(|(_, entry)| entry) {
+        if !entry.fired {
+            entry.fired = true;
+            entry.waker.wake_by_ref();
+        }
+    }
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        key: None,
+    }
+}
+
+/// Completes when `deadline` has passed. The resolution is the tick of the timer.
+pub struct Sleep {
+    deadline: Instant,
+    key: Option<Key>,
+}
+
+impl Sleep {
+    fn cancel(&mut self) {
+        if let Some(key) = self.key.take() {
+            TIMERS.lock().remove(&key);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+
+        if Instant::now() >= this.deadline {
+            this.cancel();
+            return Poll::Ready(());
+        }
+
+        let deadline = this.deadline;
+        let key = *this.key.get_or_insert_with(|| Key {
+            deadline,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        });
+
+        let entry = Entry {
+            waker: cx.waker().clone(),
+            fired: false,
+        };
+        TIMERS.lock().insert(key, entry);
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// The first item is yielded after `period`.
+pub fn interval(period: Duration) -> Interval {
+    let next = Instant::now() + period;
+
+    Interval {
+        period,
+        next,
+        sleep: sleep_until(next),
+    }
+}
+
+/// A stream which yields the scheduled instant every `period`. Ticks which are missed because the
+/// task was busy are skipped rather than yielded at once.
+pub struct Interval {
+    period: Duration,
+    next: Instant,
+    sleep: Sleep,
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Instant>> {
+        let this = self.get_mut();
+
+        if Pin::new(&mut this.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let scheduled = this.next;
+
+        this.next = scheduled + this.period;
+        let now = Instant::now();
+        if this.next <= now {
+            this.next = now + this.period;
+        }
+        this.sleep = sleep_until(this.next);
+
+        Poll::Ready(Some(scheduled))
+    }
+}
+
+/// Fails with `Elapsed` if `future` does not complete within `duration`.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+pin_project! {
+    pub struct Timeout<F> {
+        #[pin]
+        future: F,
+        sleep: Sleep,
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Elapsed;